@@ -0,0 +1,223 @@
+//! Range bias and antenna delay compensation
+//!
+//! The leading edge of a received frame is detected earlier when the signal is
+//! strong, and later when it is weak, so ranges are biased depending on the
+//! received power. The antenna delays also drift with the chip temperature.
+//! [`Compensation`] removes both errors from measured distances, using the
+//! first path power of the received frame (see [`RxQuality::rssi`]) and the
+//! chip temperature (see [`DW3000::read_temperature`]). The first path power
+//! is only measured with the `rssi` feature.
+//!
+//! [`RxQuality::rssi`]: crate::hl::RxQuality::rssi
+//! [`DW3000::read_temperature`]: crate::DW3000::read_temperature
+
+use crate::configs::{PulseRepetitionFrequency, UwbChannel};
+
+/// Range bias in m over the first path power in dBm, 16 MHz PRF
+///
+/// This table and the 64 MHz one are DW1000-era values, in the form of the
+/// range bias correction of Decawave's APS011 application note. They are not
+/// Qorvo data for the DW3000.
+const RANGE_BIAS_500MHZ_PRF16: [(f32, f32); 18] = [
+    (-95.0, 0.112),
+    (-93.0, 0.110),
+    (-91.0, 0.106),
+    (-89.0, 0.097),
+    (-87.0, 0.084),
+    (-85.0, 0.065),
+    (-83.0, 0.036),
+    (-81.0, 0.0),
+    (-79.0, -0.031),
+    (-77.0, -0.059),
+    (-75.0, -0.084),
+    (-73.0, -0.109),
+    (-71.0, -0.127),
+    (-69.0, -0.143),
+    (-67.0, -0.163),
+    (-65.0, -0.179),
+    (-63.0, -0.187),
+    (-61.0, -0.198),
+];
+
+/// Range bias in m over the first path power in dBm, 64 MHz PRF
+const RANGE_BIAS_500MHZ_PRF64: [(f32, f32); 18] = [
+    (-95.0, 0.086),
+    (-93.0, 0.081),
+    (-91.0, 0.076),
+    (-89.0, 0.071),
+    (-87.0, 0.062),
+    (-85.0, 0.049),
+    (-83.0, 0.042),
+    (-81.0, 0.035),
+    (-79.0, 0.021),
+    (-77.0, 0.0),
+    (-75.0, -0.027),
+    (-73.0, -0.051),
+    (-71.0, -0.069),
+    (-69.0, -0.082),
+    (-67.0, -0.093),
+    (-65.0, -0.100),
+    (-63.0, -0.105),
+    (-61.0, -0.110),
+];
+
+/// A lookup table, linearly interpolated between its points
+///
+/// The points are `(x, y)` pairs, strictly increasing in `x`. Outside of the
+/// table, the first or last `y` is used. An empty table is always 0.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LookupTable<'a> {
+    points: &'a [(f32, f32)],
+}
+
+impl<'a> LookupTable<'a> {
+    /// Creates a table from points sorted by `x`
+    ///
+    /// Returns `None` if `x` is not strictly increasing, or is NaN.
+    pub fn new(points: &'a [(f32, f32)]) -> Option<Self> {
+        points
+            .windows(2)
+            .all(|pair| pair[0].0 < pair[1].0)
+            .then_some(LookupTable { points })
+    }
+
+    /// A table that is always 0
+    pub const fn empty() -> Self {
+        LookupTable { points: &[] }
+    }
+
+    /// Returns the interpolated value at `x`
+    pub fn get(&self, x: f32) -> f32 {
+        let (first, last) = match (self.points.first(), self.points.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return 0.0,
+        };
+        if x <= first.0 {
+            return first.1;
+        }
+        if x >= last.0 {
+            return last.1;
+        }
+
+        let upper = match self.points.iter().position(|&(px, _)| px >= x) {
+            Some(upper) => upper,
+            // Only if `x` is NaN
+            None => return last.1,
+        };
+        let (x0, y0) = self.points[upper - 1];
+        let (x1, y1) = self.points[upper];
+
+        y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+    }
+}
+
+/// Corrects measured distances for the range bias and the antenna delay drift
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Compensation<'a> {
+    /// The range bias in m over the first path power in dBm
+    ///
+    /// The bias is the measured minus the true distance.
+    pub range_bias: LookupTable<'a>,
+    /// The distance error caused by the antenna delay drift, in m over the
+    /// chip temperature in °C
+    ///
+    /// This depends on the board and antenna, so there is no default. It
+    /// should be 0 at the temperature the antenna delays were calibrated at.
+    pub antenna_delay: LookupTable<'a>,
+}
+
+impl Compensation<'static> {
+    /// Creates a compensation with the default range bias table for the PRF
+    ///
+    /// The tables are DW1000-era values, not measured on the DW3000, and
+    /// channels 5 and 9 share them. Replace them with
+    /// [`Compensation::with_range_bias`] once the board is calibrated. No
+    /// antenna delay correction is applied.
+    pub fn new(channel: UwbChannel, prf: PulseRepetitionFrequency) -> Self {
+        let range_bias: &'static [(f32, f32)] = match (channel, prf) {
+            (UwbChannel::Channel5 | UwbChannel::Channel9, PulseRepetitionFrequency::Mhz16) => {
+                &RANGE_BIAS_500MHZ_PRF16
+            }
+            (UwbChannel::Channel5 | UwbChannel::Channel9, PulseRepetitionFrequency::Mhz64) => {
+                &RANGE_BIAS_500MHZ_PRF64
+            }
+        };
+
+        Compensation {
+            // The tables are sorted, see the `default_tables` test
+            range_bias: LookupTable { points: range_bias },
+            antenna_delay: LookupTable::empty(),
+        }
+    }
+}
+
+impl<'a> Compensation<'a> {
+    /// Replaces the range bias table
+    pub fn with_range_bias(mut self, table: LookupTable<'a>) -> Self {
+        self.range_bias = table;
+        self
+    }
+
+    /// Replaces the antenna delay table
+    pub fn with_antenna_delay(mut self, table: LookupTable<'a>) -> Self {
+        self.antenna_delay = table;
+        self
+    }
+
+    /// Returns the error of a distance measured at the given first path power
+    /// (dBm) and chip temperature (°C), in m
+    pub fn error(&self, first_path_power: f32, temperature: f32) -> f32 {
+        self.range_bias.get(first_path_power) + self.antenna_delay.get(temperature)
+    }
+
+    /// Corrects a distance in m, measured at the given first path power (dBm)
+    /// and chip temperature (°C)
+    pub fn correct_distance(&self, distance: f32, first_path_power: f32, temperature: f32) -> f32 {
+        distance - self.error(first_path_power, temperature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn lookup_table() {
+        let table = LookupTable::new(&[(-10.0, 1.0), (0.0, 2.0), (10.0, 0.0)]).unwrap();
+
+        assert_close(table.get(-20.0), 1.0);
+        assert_close(table.get(-5.0), 1.5);
+        assert_close(table.get(0.0), 2.0);
+        assert_close(table.get(2.5), 1.5);
+        assert_close(table.get(20.0), 0.0);
+        assert_close(LookupTable::empty().get(1.0), 0.0);
+    }
+
+    #[test]
+    fn unsorted_lookup_table() {
+        assert_eq!(LookupTable::new(&[(0.0, 1.0), (-10.0, 2.0)]), None);
+        assert_eq!(LookupTable::new(&[(0.0, 1.0), (0.0, 2.0)]), None);
+        assert_eq!(LookupTable::new(&[(0.0, 1.0), (f32::NAN, 2.0)]), None);
+    }
+
+    #[test]
+    fn default_tables() {
+        assert!(LookupTable::new(&RANGE_BIAS_500MHZ_PRF16).is_some());
+        assert!(LookupTable::new(&RANGE_BIAS_500MHZ_PRF64).is_some());
+    }
+
+    #[test]
+    fn correct_distance() {
+        let compensation = Compensation::new(UwbChannel::Channel5, PulseRepetitionFrequency::Mhz64)
+            .with_antenna_delay(LookupTable::new(&[(20.0, 0.0), (60.0, 0.04)]).unwrap());
+
+        // Strong signals are measured too short
+        assert_close(compensation.correct_distance(1.0, -61.0, 20.0), 1.110);
+        assert_close(compensation.correct_distance(10.0, -76.0, 20.0), 10.0135);
+        assert_close(compensation.correct_distance(10.0, -77.0, 40.0), 9.98);
+    }
+}
@@ -4,6 +4,8 @@
 //! transmitted and received. The configs are passed to the send and receive
 //! functions.
 
+use crate::time::Duration;
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 /// General configuration for TX and RX
 pub struct Config {
@@ -17,8 +19,9 @@ pub struct Config {
     pub preamble_length: PreambleLength,
     /// Sets the bitrate of the transmission.
     pub bitrate: BitRate,
-    /// Defaults to `false`.
-    pub frame_filtering: bool,
+    /// The frame filter, or `None` to receive all frames.
+    /// Defaults to `None`.
+    pub frame_filter: Option<FrameFilter>,
     /// Sets the ranging bit in the transmitted frame.
     /// This has no effect on the capabilities of the DW3000.
     pub ranging_enable: bool,
@@ -42,6 +45,78 @@ pub struct Config {
     pub pdoa_mode: PdoaMode,
     /// When enabled, the radio itself will send acks to messages with the ack bit enabled.
     pub auto_ack: AutoAck,
+    /// How the receiver listens for a preamble. Defaults to continuous.
+    pub receive_mode: ReceiveMode,
+    /// Receive frame wait timeout, optional
+    ///
+    /// If no frame was received within this time after the receiver was
+    /// enabled, the receiver turns off and reports `Error::FrameWaitTimeout`.
+    /// The resolution is 512 / 499.2 MHz (about 1.026 µs), up to about 17.2 s.
+    pub rx_frame_wait_timeout: Option<Duration>,
+    /// Preamble detection timeout in PAC units, optional
+    ///
+    /// If no preamble was detected within this many PACs (plus one) after the
+    /// receiver was enabled, the receiver turns off and reports
+    /// `Error::PreambleDetectionTimeout`. Must not be 0.
+    pub rx_preamble_timeout: Option<u16>,
+}
+
+impl Config {
+    /// Returns the time the preamble, SFD and STS take on air
+    pub fn preamble_duration(&self) -> Duration {
+        let sfd_symbols = match self.sfd_sequence {
+            SfdSequence::Decawave16 => 16,
+            _ => 8,
+        };
+        let sts_symbols = match self.sts_mode {
+            StsMode::StsModeOff => 0,
+            _ => self.sts_len.get_sts_length() as u64,
+        };
+        let symbols = self.preamble_length.get_num_of_symbols() as u64 + sfd_symbols + sts_symbols;
+
+        // The preamble symbol duration depends on the PRF
+        let symbol_picos = match self.pulse_repetition_frequency {
+            PulseRepetitionFrequency::Mhz16 => 993_590,
+            PulseRepetitionFrequency::Mhz64 => 1_017_630,
+        };
+
+        duration_from_picos(symbols * symbol_picos)
+    }
+
+    /// Returns the time a frame of `len` bytes, including the FCS, takes on air
+    pub fn frame_duration(&self, len: usize) -> Duration {
+        // Bit durations at 850 kbps and 6.8 Mbps
+        let bit_picos = |bitrate| match bitrate {
+            BitRate::Kbps850 => 1_176_471,
+            BitRate::Kbps6800 => 147_059,
+        };
+
+        // The PHR has 21 bits, including its SECDED check bits
+        let phr_bitrate = match self.phr_rate {
+            PhrRate::Standard => BitRate::Kbps850,
+            PhrRate::DataRate => self.bitrate,
+        };
+        let phr_picos = 21 * bit_picos(phr_bitrate);
+
+        // The Reed-Solomon encoder adds 48 parity bits per block of up to 330
+        // data bits
+        let data_bits = len as u64 * 8;
+        let data_bits = data_bits + 48 * data_bits.div_ceil(330);
+        let data_picos = data_bits * bit_picos(self.bitrate);
+
+        let preamble = self.preamble_duration().value();
+        let rest = duration_from_picos(phr_picos + data_picos).value();
+
+        // 127 bytes take about 1.2 ms even with the longest preamble, far
+        // below the 40-bit limit
+        Duration::new(preamble + rest).unwrap()
+    }
+}
+
+/// Converts picoseconds to DW3000 time units, 1 ps being 0.0638976 units
+fn duration_from_picos(picos: u64) -> Duration {
+    // Airtimes of at most a few ms can't overflow or exceed 40 bits
+    Duration::new((picos * 638976 + 5_000_000) / 10_000_000).unwrap()
 }
 
 impl Default for Config {
@@ -52,7 +127,7 @@ impl Default for Config {
             pulse_repetition_frequency: Default::default(),
             preamble_length: Default::default(),
             bitrate: Default::default(),
-            frame_filtering: false,
+            frame_filter: None,
             ranging_enable: false,
             sts_mode: Default::default(), //mode off
             sts_len: Default::default(),
@@ -63,6 +138,9 @@ impl Default for Config {
             phr_rate: Default::default(),
             pdoa_mode: Default::default(),
             auto_ack: Default::default(),
+            receive_mode: Default::default(),
+            rx_frame_wait_timeout: None,
+            rx_preamble_timeout: None,
         }
     }
 }
@@ -77,6 +155,116 @@ pub enum TxContinuation {
     Rx,
 }
 
+/// Which frames are accepted by the frame filter
+///
+/// Frames that don't pass the filter are rejected by the receiver. The fields
+/// map to the bits of the `FF_CFG` register. The default accepts beacon, data,
+/// acknowledgement and MAC command frames.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FrameFilter {
+    /// Accept beacon frames
+    pub allow_beacon: bool,
+    /// Accept data frames
+    pub allow_data: bool,
+    /// Accept acknowledgement frames
+    pub allow_ack: bool,
+    /// Accept MAC command frames
+    pub allow_mac_command: bool,
+    /// Accept frames with a reserved frame type
+    pub allow_reserved: bool,
+    /// Accept multipurpose frames
+    pub allow_multipurpose: bool,
+    /// Accept fragmented frames
+    pub allow_fragmented: bool,
+    /// Accept frames with the extended frame type
+    pub allow_extended: bool,
+    /// Behave as coordinator, accepting frames without a destination address
+    /// if their source PAN ID matches ours
+    pub behave_as_coordinator: bool,
+    /// Accept frames without a destination address (MAC implicit broadcast)
+    pub allow_mac_implicit_broadcast: bool,
+    /// Re-enable the receiver automatically after a rejected or erroneous
+    /// frame
+    pub auto_reenable: bool,
+}
+
+impl Default for FrameFilter {
+    fn default() -> Self {
+        FrameFilter {
+            allow_beacon: true,
+            allow_data: true,
+            allow_ack: true,
+            allow_mac_command: true,
+            allow_reserved: false,
+            allow_multipurpose: false,
+            allow_fragmented: false,
+            allow_extended: false,
+            behave_as_coordinator: false,
+            allow_mac_implicit_broadcast: false,
+            auto_reenable: true,
+        }
+    }
+}
+
+/// How the receiver hunts for a preamble
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Default)]
+pub enum ReceiveMode {
+    #[default]
+    /// The receiver is on all the time
+    Continuous,
+    /// The receiver is duty-cycled while it is looking for a preamble
+    ///
+    /// This saves a large part of the RX current, but the transmitter has to
+    /// use a preamble that is long enough to be caught by the receiver. See
+    /// [`ReceiveMode::min_tx_preamble_length`].
+    Sniff {
+        /// The ON time in PAC units, 1 to 15. The chip adds one PAC.
+        on_pacs: u8,
+        /// The OFF time in µs
+        off_us: u8,
+    },
+}
+
+impl ReceiveMode {
+    /// Returns the shortest preamble a transmitter should use, so a receiver
+    /// in this mode still detects its frames
+    ///
+    /// `rx_preamble_length` is the preamble length the receiver is configured
+    /// with, which determines its PAC size. Returns `None` if no supported
+    /// preamble is long enough.
+    pub fn min_tx_preamble_length(
+        &self,
+        rx_preamble_length: PreambleLength,
+    ) -> Option<PreambleLength> {
+        let (on_pacs, off_us) = match *self {
+            ReceiveMode::Continuous => return Some(rx_preamble_length),
+            ReceiveMode::Sniff { on_pacs, off_us } => (on_pacs, off_us),
+        };
+
+        let on_symbols = (on_pacs as usize + 1) * rx_preamble_length.get_pac_size_in_symbols();
+        // The shortest preamble symbol is 993.59 ns (16 MHz PRF)
+        let off_symbols = (off_us as usize * 1000).div_ceil(993);
+        // The preamble has to cover a full OFF period and an ON period that
+        // may have started just before the preamble did
+        let required = off_symbols + 2 * on_symbols;
+
+        [
+            PreambleLength::Symbols32,
+            PreambleLength::Symbols64,
+            PreambleLength::Symbols72,
+            PreambleLength::Symbols128,
+            PreambleLength::Symbols256,
+            PreambleLength::Symbols512,
+            PreambleLength::Symbols1024,
+            PreambleLength::Symbols1536,
+            PreambleLength::Symbols2048,
+            PreambleLength::Symbols4096,
+        ]
+        .into_iter()
+        .find(|length| length.get_num_of_symbols() >= required)
+    }
+}
+
 /// The auto acknowledge behavior
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Default)]
 pub enum AutoAck {
@@ -183,6 +371,17 @@ impl PreambleLength {
         }
     }
 
+    /// Gets the size in symbols of the recommended PAC (see
+    /// `get_recommended_pac_size`)
+    pub fn get_pac_size_in_symbols(&self) -> usize {
+        match self.get_recommended_pac_size() {
+            0 => 8,
+            1 => 16,
+            2 => 32,
+            _ => 4,
+        }
+    }
+
     /// Get the number of symbols in the preamble
     pub fn get_num_of_symbols(&self) -> usize {
         match self {
@@ -466,3 +665,53 @@ pub enum PhrRate {
     /// PHR at data rate (6.8 Mbps)
     DataRate = 1,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn airtime() {
+        let config = Config::default();
+
+        // 128 + 8 symbols of 1017.63 ns, about 138.4 µs
+        assert_eq!(
+            config.preamble_duration(),
+            Duration::new(8_843_280).unwrap()
+        );
+
+        // 21 PHR bits and 80 + 48 data bits at 850 kbps, about 175.3 µs more
+        assert_eq!(
+            config.frame_duration(10),
+            Duration::new(20_044_157).unwrap()
+        );
+    }
+
+    #[test]
+    fn min_tx_preamble_length() {
+        let continuous = ReceiveMode::Continuous;
+        assert_eq!(
+            continuous.min_tx_preamble_length(PreambleLength::Symbols128),
+            Some(PreambleLength::Symbols128)
+        );
+
+        // 2 * 3 PACs of 16 symbols + 64 µs off time
+        let sniff = ReceiveMode::Sniff {
+            on_pacs: 2,
+            off_us: 64,
+        };
+        assert_eq!(
+            sniff.min_tx_preamble_length(PreambleLength::Symbols128),
+            Some(PreambleLength::Symbols256)
+        );
+
+        let sniff = ReceiveMode::Sniff {
+            on_pacs: 15,
+            off_us: 255,
+        };
+        assert_eq!(
+            sniff.min_tx_preamble_length(PreambleLength::Symbols128),
+            Some(PreambleLength::Symbols1024)
+        );
+    }
+}
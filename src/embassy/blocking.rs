@@ -0,0 +1,226 @@
+use core::{
+    cell::RefCell,
+    task::{Context, Waker},
+};
+
+use critical_section::Mutex;
+use embassy_net_driver::{Capabilities, HardwareAddress, LinkState};
+use embedded_hal::digital::InputPin;
+use smoltcp::phy::{self, Device};
+
+use crate::{
+    phy::{Phy, MAX_FRAME_LEN},
+    Config, Error, Ready, DW3000,
+};
+
+/// Wakes the network stack when the IRQ pin of the DW3000 goes high
+pub struct IrqWaker {
+    waker: Mutex<RefCell<Option<Waker>>>,
+}
+
+impl IrqWaker {
+    /// Creates a waker that can be put in a `static`
+    pub const fn new() -> Self {
+        IrqWaker {
+            waker: Mutex::new(RefCell::new(None)),
+        }
+    }
+
+    /// Wakes the network stack
+    ///
+    /// Call this when the IRQ pin goes high.
+    pub fn wake(&self) {
+        let waker = critical_section::with(|cs| self.waker.borrow_ref_mut(cs).take());
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    fn register(&self, waker: &Waker) {
+        critical_section::with(|cs| {
+            let mut slot = self.waker.borrow_ref_mut(cs);
+            match &*slot {
+                Some(registered) if registered.will_wake(waker) => {}
+                _ => *slot = Some(waker.clone()),
+            }
+        });
+    }
+}
+
+impl Default for IrqWaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Blocking `embassy-net` driver for the IEEE 802.15.4 medium
+///
+/// `embassy-net` polls its driver from a synchronous context, so the SPI bus
+/// is used in blocking mode, which embassy HALs support as well. The driver is
+/// built on top of [`Phy`].
+///
+/// The IRQ pin can't register a waker by itself. Await the pin in a task or
+/// interrupt handler, and call [`IrqWaker::wake`] when it goes high:
+///
+/// ```ignore
+/// static IRQ_WAKER: IrqWaker = IrqWaker::new();
+///
+/// #[embassy_executor::task]
+/// async fn irq_task(mut irq: ExtiInput<'static>) {
+///     loop {
+///         irq.wait_for_high().await;
+///         IRQ_WAKER.wake();
+///         irq.wait_for_low().await;
+///     }
+/// }
+/// ```
+///
+/// The hardware address is the EUI of the DW3000. The PAN identifier and short
+/// address in `PANADR` are left as configured, and are still used by the
+/// frame filter.
+///
+/// The link is reported as down once the radio was lost to an SPI error.
+pub struct NetDriver<'a, SPI, IRQ>
+where
+    SPI: embedded_hal::spi::SpiDevice<u8>,
+{
+    phy: Phy<SPI>,
+    irq: IRQ,
+    waker: &'a IrqWaker,
+    eui: [u8; 8],
+}
+
+impl<'a, SPI, IRQ> NetDriver<'a, SPI, IRQ>
+where
+    SPI: embedded_hal::spi::SpiDevice<u8>,
+    IRQ: InputPin,
+{
+    /// Creates a driver from an initialized radio
+    ///
+    /// Enables the receive interrupts on the DW3000, so the IRQ pin goes high
+    /// when a reception is over. `config` is used for both sending and
+    /// receiving.
+    pub fn new(
+        mut radio: DW3000<SPI, Ready>,
+        config: Config,
+        irq: IRQ,
+        waker: &'a IrqWaker,
+    ) -> Result<Self, Error<SPI>> {
+        radio.enable_rx_interrupts()?;
+        let eui = radio.ll().eui().read()?.value();
+
+        Ok(NetDriver {
+            phy: Phy::new(radio, config),
+            irq,
+            waker,
+            eui: eui.to_be_bytes(),
+        })
+    }
+
+    /// Returns the radio and the IRQ pin, unless the radio was lost to an SPI
+    /// error
+    pub fn release(self) -> Option<(DW3000<SPI, Ready>, IRQ)> {
+        let irq = self.irq;
+        self.phy.release().map(|radio| (radio, irq))
+    }
+}
+
+impl<SPI, IRQ> embassy_net_driver::Driver for NetDriver<'_, SPI, IRQ>
+where
+    SPI: embedded_hal::spi::SpiDevice<u8>,
+    IRQ: InputPin,
+{
+    type RxToken<'a>
+        = RxToken<'a>
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = TxToken<'a, SPI>
+    where
+        Self: 'a;
+
+    fn receive(&mut self, cx: &mut Context) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        // Register before checking the pin, so an interrupt in between isn't
+        // missed
+        self.waker.register(cx.waker());
+
+        // The pin is only checked as a shortcut. The status register tells
+        // whether a frame has arrived, and `Phy` turns the receiver on when
+        // it's off.
+        if !self.phy.is_receiving() || self.irq.is_high().unwrap_or(true) {
+            let (rx, tx) = self.phy.receive(smoltcp::time::Instant::ZERO)?;
+            return Some((RxToken { inner: rx }, TxToken { inner: tx }));
+        }
+
+        None
+    }
+
+    fn transmit(&mut self, _cx: &mut Context) -> Option<Self::TxToken<'_>> {
+        // Sending blocks until the frame is out, so there's never a wait for
+        // the transmitter
+        let tx = self.phy.transmit(smoltcp::time::Instant::ZERO)?;
+
+        Some(TxToken { inner: tx })
+    }
+
+    fn link_state(&mut self, _cx: &mut Context) -> LinkState {
+        // A lost radio never comes back, so there's nothing to wake up for
+        if self.phy.is_lost() {
+            LinkState::Down
+        } else {
+            LinkState::Up
+        }
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        let mut capabilities = Capabilities::default();
+        capabilities.max_transmission_unit = self.phy.capabilities().max_transmission_unit;
+
+        capabilities
+    }
+
+    fn hardware_address(&self) -> HardwareAddress {
+        HardwareAddress::Ieee802154(self.eui)
+    }
+}
+
+/// Receive token of [`NetDriver`]
+pub struct RxToken<'a> {
+    inner: crate::phy::RxToken<'a>,
+}
+
+impl embassy_net_driver::RxToken for RxToken<'_> {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        phy::RxToken::consume(self.inner, |frame| {
+            // embassy-net wants to modify frames in place
+            let mut buffer = [0; MAX_FRAME_LEN];
+            let buffer = &mut buffer[..frame.len()];
+            buffer.copy_from_slice(frame);
+
+            f(buffer)
+        })
+    }
+}
+
+/// Transmit token of [`NetDriver`]
+pub struct TxToken<'a, SPI>
+where
+    SPI: embedded_hal::spi::SpiDevice<u8>,
+{
+    inner: crate::phy::TxToken<'a, SPI>,
+}
+
+impl<SPI> embassy_net_driver::TxToken for TxToken<'_, SPI>
+where
+    SPI: embedded_hal::spi::SpiDevice<u8>,
+{
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        phy::TxToken::consume(self.inner, len, f)
+    }
+}
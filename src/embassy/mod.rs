@@ -0,0 +1,26 @@
+//! [`embassy_net_driver::Driver`] implementation
+//!
+//! Makes the DW3000 a link for `embassy-net` stacks with the IEEE 802.15.4
+//! medium. The hardware address is the EUI of the DW3000. The PAN identifier
+//! and short address in `PANADR` are left as configured, and are still used by
+//! the frame filter.
+//!
+//! `embassy-net` polls its driver from a synchronous context, so the driver
+//! depends on the `async` feature:
+//!
+//! - With the `async` feature, which is enabled by default, `new` splits the
+//!   radio into a `Runner` and a `Device`. The runner owns the radio and waits
+//!   on the IRQ pin, and has to be run in its own task. The device is handed
+//!   to the `embassy-net` stack, and exchanges frames with the runner.
+//! - Without it, `NetDriver` drives the radio from the stack's poll with the
+//!   blocking SPI bus, and an `IrqWaker` wakes the stack on interrupts.
+
+#[cfg(not(feature = "async"))]
+mod blocking;
+#[cfg(feature = "async")]
+mod runner;
+
+#[cfg(not(feature = "async"))]
+pub use blocking::*;
+#[cfg(feature = "async")]
+pub use runner::*;
@@ -0,0 +1,424 @@
+use core::{
+    cell::RefCell,
+    future::{poll_fn, Future},
+    pin::pin,
+    task::{Context, Poll, Waker},
+};
+
+use critical_section::Mutex;
+use embassy_net_driver::{Capabilities, HardwareAddress, LinkState};
+use embedded_hal_async::{digital::Wait, spi::SpiDevice};
+
+use crate::{configs::TxContinuation, hl::SendTime, Config, Error, Ready, DW3000};
+
+/// The largest frame the DW3000 sends and receives, including the FCS
+const MAX_FRAME_LEN: usize = 127;
+
+/// The length of the FCS, which the DW3000 appends and checks by itself
+const FCS_LEN: usize = 2;
+
+/// The largest frame exchanged with the stack
+const MTU: usize = MAX_FRAME_LEN - FCS_LEN;
+
+struct Shared {
+    rx: [u8; MTU],
+    rx_len: Option<usize>,
+    tx: [u8; MTU],
+    tx_len: Option<usize>,
+    link_up: bool,
+    device_waker: Option<Waker>,
+    runner_waker: Option<Waker>,
+}
+
+/// The frames in flight between a [`Runner`] and its [`Device`]
+///
+/// Holds one received frame and one frame to send. Put it in a `static`, so
+/// both halves can borrow it:
+///
+/// ```ignore
+/// static STATE: StaticCell<State> = StaticCell::new();
+///
+/// let state = STATE.init(State::new());
+/// let (runner, device) = dw3000_ng::embassy::new(radio, config, irq, state).await?;
+/// spawner.spawn(radio_task(runner)).unwrap();
+/// ```
+pub struct State {
+    shared: Mutex<RefCell<Shared>>,
+}
+
+impl State {
+    /// Creates an empty state
+    pub const fn new() -> Self {
+        State {
+            shared: Mutex::new(RefCell::new(Shared {
+                rx: [0; MTU],
+                rx_len: None,
+                tx: [0; MTU],
+                tx_len: None,
+                link_up: false,
+                device_waker: None,
+                runner_waker: None,
+            })),
+        }
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut Shared) -> R) -> R {
+        critical_section::with(|cs| f(&mut self.shared.borrow_ref_mut(cs)))
+    }
+
+    /// Wakes the device after `f` changed the shared state
+    fn wake_device(&self, f: impl FnOnce(&mut Shared)) {
+        let waker = self.with(|shared| {
+            f(shared);
+            shared.device_waker.take()
+        });
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Wakes the runner after `f` changed the shared state
+    fn wake_runner(&self, f: impl FnOnce(&mut Shared)) {
+        let waker = self.with(|shared| {
+            f(shared);
+            shared.runner_waker.take()
+        });
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// Returns `Ready` once `ready` is true, registering the runner otherwise
+    fn poll_runner(&self, cx: &Context, ready: impl Fn(&Shared) -> bool) -> Poll<()> {
+        self.with(|shared| {
+            if ready(shared) {
+                Poll::Ready(())
+            } else {
+                register(&mut shared.runner_waker, cx.waker());
+                Poll::Pending
+            }
+        })
+    }
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn register(slot: &mut Option<Waker>, waker: &Waker) {
+    match slot {
+        Some(registered) if registered.will_wake(waker) => {}
+        _ => *slot = Some(waker.clone()),
+    }
+}
+
+/// Splits an initialized radio into a [`Runner`] and a [`Device`]
+///
+/// Enables the transmit and receive interrupts on the DW3000, so the IRQ pin
+/// goes high when a transmission or reception is over. `config` is used for
+/// both sending and receiving.
+#[allow(clippy::type_complexity)]
+pub async fn new<'a, SPI, IRQ>(
+    mut radio: DW3000<SPI, Ready>,
+    config: Config,
+    irq: IRQ,
+    state: &'a State,
+) -> Result<(Runner<'a, SPI, IRQ>, Device<'a>), Error<SPI>>
+where
+    SPI: SpiDevice<u8>,
+    IRQ: Wait,
+{
+    radio.enable_tx_interrupts().await?;
+    radio.enable_rx_interrupts().await?;
+    let eui = radio.ll().eui().read().await?.value();
+
+    state.wake_device(|shared| shared.link_up = true);
+
+    Ok((
+        Runner {
+            radio,
+            irq,
+            config,
+            state,
+        },
+        Device {
+            state,
+            eui: eui.to_be_bytes(),
+        },
+    ))
+}
+
+/// Owns the radio and exchanges frames with the [`Device`]
+///
+/// The receiver is kept on while there is room for a received frame, and is
+/// turned off for each transmission.
+pub struct Runner<'a, SPI, IRQ> {
+    radio: DW3000<SPI, Ready>,
+    irq: IRQ,
+    config: Config,
+    state: &'a State,
+}
+
+impl<SPI, IRQ> Runner<'_, SPI, IRQ>
+where
+    SPI: SpiDevice<u8>,
+    IRQ: Wait,
+{
+    /// Sends and receives frames until the radio fails
+    ///
+    /// Only returns on an SPI or IRQ pin error, which loses the radio. The
+    /// link is reported as down by then.
+    pub async fn run(self) -> Error<SPI> {
+        let Runner {
+            mut radio,
+            mut irq,
+            config,
+            state,
+        } = self;
+
+        let error = loop {
+            radio = match step(radio, &mut irq, config, state).await {
+                Ok(radio) => radio,
+                Err(error) => break error,
+            };
+        };
+
+        state.wake_device(|shared| shared.link_up = false);
+
+        error
+    }
+}
+
+/// Sends a queued frame, or receives one if there is room for it
+async fn step<SPI, IRQ>(
+    radio: DW3000<SPI, Ready>,
+    irq: &mut IRQ,
+    config: Config,
+    state: &State,
+) -> Result<DW3000<SPI, Ready>, Error<SPI>>
+where
+    SPI: SpiDevice<u8>,
+    IRQ: Wait,
+{
+    let mut frame = [0; MTU];
+    let mut tx_len = None;
+    state.wake_device(|shared| {
+        tx_len = shared.tx_len.take();
+        frame = shared.tx;
+    });
+    if let Some(len) = tx_len {
+        return send(radio, irq, config, &frame[..len]).await;
+    }
+
+    // Wait until the stack took the last frame, or has one to send
+    if state.with(|shared| shared.rx_len.is_some()) {
+        poll_fn(|cx| {
+            state.poll_runner(cx, |shared| {
+                shared.rx_len.is_none() || shared.tx_len.is_some()
+            })
+        })
+        .await;
+        return Ok(radio);
+    }
+
+    receive(radio, irq, config, state).await
+}
+
+async fn send<SPI, IRQ>(
+    radio: DW3000<SPI, Ready>,
+    irq: &mut IRQ,
+    config: Config,
+    frame: &[u8],
+) -> Result<DW3000<SPI, Ready>, Error<SPI>>
+where
+    SPI: SpiDevice<u8>,
+    IRQ: Wait,
+{
+    let mut sending = radio
+        .send_raw(frame, SendTime::Now, TxContinuation::Ready, config)
+        .await?;
+    let result = sending.wait_sent(irq).await;
+    let radio = sending.finish_sending().await.map_err(|(_, error)| error)?;
+
+    match result {
+        Err(error @ (Error::Spi(_) | Error::Irq)) => Err(error),
+        // The frame was sent, but its timestamp is not needed here
+        _ => Ok(radio),
+    }
+}
+
+async fn receive<SPI, IRQ>(
+    radio: DW3000<SPI, Ready>,
+    irq: &mut IRQ,
+    config: Config,
+    state: &State,
+) -> Result<DW3000<SPI, Ready>, Error<SPI>>
+where
+    SPI: SpiDevice<u8>,
+    IRQ: Wait,
+{
+    let mut receiving = radio.receive(config).await?;
+
+    let mut buffer = [0; MAX_FRAME_LEN];
+    let result = loop {
+        match receiving.r_wait_buf(&mut buffer).await {
+            Err(nb::Error::WouldBlock) => {}
+            result => break Some(result),
+        }
+
+        // Sleep until the reception is over, or the stack has a frame to send
+        let mut high = pin!(irq.wait_for_high());
+        let tx_pending = poll_fn(|cx| {
+            if let Poll::Ready(result) = high.as_mut().poll(cx) {
+                return Poll::Ready(result.map(|()| false));
+            }
+            state
+                .poll_runner(cx, |shared| shared.tx_len.is_some())
+                .map(|()| Ok(true))
+        })
+        .await
+        .map_err(|_| Error::Irq)?;
+
+        if tx_pending {
+            break None;
+        }
+    };
+
+    let radio = receiving
+        .finish_receiving()
+        .await
+        .map_err(|(_, error)| error)?;
+
+    match result {
+        Some(Ok((len, _, _))) if len >= FCS_LEN => {
+            let frame = &buffer[..len - FCS_LEN];
+            state.wake_device(|shared| {
+                shared.rx[..frame.len()].copy_from_slice(frame);
+                shared.rx_len = Some(frame.len());
+            });
+        }
+        Some(Err(nb::Error::Other(Error::Spi(error)))) => return Err(Error::Spi(error)),
+        // Reception errors, the frame is dropped
+        _ => {}
+    }
+
+    Ok(radio)
+}
+
+/// `embassy-net` driver for the IEEE 802.15.4 medium
+///
+/// Hand this to the `embassy-net` stack. Frames are sent and received by its
+/// [`Runner`]. The link is reported as down once the runner stopped.
+pub struct Device<'a> {
+    state: &'a State,
+    eui: [u8; 8],
+}
+
+impl embassy_net_driver::Driver for Device<'_> {
+    type RxToken<'a>
+        = RxToken<'a>
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = TxToken<'a>
+    where
+        Self: 'a;
+
+    fn receive(&mut self, cx: &mut Context) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let ready = self.state.with(|shared| {
+            let ready = shared.rx_len.is_some() && shared.tx_len.is_none();
+            if !ready {
+                register(&mut shared.device_waker, cx.waker());
+            }
+            ready
+        });
+
+        ready.then_some((RxToken { state: self.state }, TxToken { state: self.state }))
+    }
+
+    fn transmit(&mut self, cx: &mut Context) -> Option<Self::TxToken<'_>> {
+        let ready = self.state.with(|shared| {
+            let ready = shared.tx_len.is_none();
+            if !ready {
+                register(&mut shared.device_waker, cx.waker());
+            }
+            ready
+        });
+
+        ready.then_some(TxToken { state: self.state })
+    }
+
+    fn link_state(&mut self, cx: &mut Context) -> LinkState {
+        self.state.with(|shared| {
+            register(&mut shared.device_waker, cx.waker());
+            if shared.link_up {
+                LinkState::Up
+            } else {
+                LinkState::Down
+            }
+        })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        let mut capabilities = Capabilities::default();
+        capabilities.max_transmission_unit = MTU;
+
+        capabilities
+    }
+
+    fn hardware_address(&self) -> HardwareAddress {
+        HardwareAddress::Ieee802154(self.eui)
+    }
+}
+
+/// Receive token of [`Device`]
+pub struct RxToken<'a> {
+    state: &'a State,
+}
+
+impl embassy_net_driver::RxToken for RxToken<'_> {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut frame = [0; MTU];
+        let mut len = 0;
+        // Frees the slot for the next frame
+        self.state.wake_runner(|shared| {
+            len = shared.rx_len.take().unwrap_or(0);
+            frame = shared.rx;
+        });
+
+        f(&mut frame[..len])
+    }
+}
+
+/// Transmit token of [`Device`]
+pub struct TxToken<'a> {
+    state: &'a State,
+}
+
+impl embassy_net_driver::TxToken for TxToken<'_> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut frame = [0; MTU];
+
+        // embassy-net doesn't ask for more than the MTU. If it does anyway,
+        // the frame is cut short and dropped.
+        if len > MTU {
+            return f(&mut frame);
+        }
+
+        let result = f(&mut frame[..len]);
+        self.state.wake_runner(|shared| {
+            shared.tx[..len].copy_from_slice(&frame[..len]);
+            shared.tx_len = Some(len);
+        });
+
+        result
+    }
+}
@@ -0,0 +1,674 @@
+//! Typed handles for the DW3000 GPIOs
+//!
+//! Each of [`Gpio0`] to [`Gpio8`] owns its own SPI device, so it can be used
+//! next to the radio. Share the bus between the radio and the handles the same
+//! way as between any other devices, for example with the `embedded-hal-bus`
+//! or `embassy-embedded-hal` shared bus devices.
+//!
+//! A handle starts in the [`Unconfigured`] mode, which leaves the pin as it
+//! is. [`Gpio::into_output`] and [`Gpio::into_input`] set the direction of
+//! the pin.
+//!
+//! The `embedded_hal::digital` traits are blocking, so the handles only
+//! implement them without the `async` feature, which is enabled by default.
+//! Use `default-features = false` to pass the handles to drivers that expect
+//! those traits. With the `async` feature, use the inherent async methods of
+//! the same names instead.
+//!
+//! The handles only access the GPIO registers that the radio doesn't change
+//! by itself. The GPIO clocks, the pin modes in `GPIO_MODE` and the `GPIO`
+//! interrupt event share their registers with other functions of the radio, so
+//! they are set through the radio instead. Switch a pin from its alternate
+//! function (LED, external PA, SYNC or IRQ) to GPIO with
+//! [`DW3000::select_gpio_mode`](crate::DW3000::select_gpio_mode) before
+//! configuring it:
+//!
+//! ```ignore
+//! let gpio: Gpio5<_> = Gpio::new(gpio_spi);
+//! radio.select_gpio_mode(&gpio).await?;
+//! let mut gpio = gpio.into_output(false).await?;
+//! ```
+//!
+//! Only the bit of the handle's own pin is changed in the GPIO registers, but
+//! this takes a read and a write. Handles that are used concurrently, for
+//! example from different tasks, must not interrupt each other's accesses, and
+//! neither must [`DW3000::gpio_config`](crate::DW3000::gpio_config).
+
+use core::marker::PhantomData;
+
+use crate::{ll, maybe_async_attr, spi_type, Error};
+
+/// Pin mode of a handle that hasn't been configured yet
+pub struct Unconfigured;
+
+/// Pin mode of a GPIO input
+pub struct Input;
+
+/// Pin mode of a GPIO output
+pub struct Output;
+
+bitflags::bitflags! {
+    /// The LED functions of GPIO0 to GPIO3
+    ///
+    /// See [`DW3000::enable_leds`](crate::DW3000::enable_leds).
+    #[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+    pub struct Led: u8 {
+        /// GPIO0, blinks when a good frame is received
+        const RX_OK = 1 << 0;
+        /// GPIO1, blinks when an SFD is detected
+        const SFD = 1 << 1;
+        /// GPIO2, blinks when the receiver is turned on
+        const RX = 1 << 2;
+        /// GPIO3, blinks when a frame is sent
+        const TX = 1 << 3;
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Led {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "Led({=u8:#x})", self.bits())
+    }
+}
+
+impl Led {
+    /// Converts a blink duration to the `BLINK_TIM` count, which is in units
+    /// of 14 ms
+    ///
+    /// Returns `None` if the duration is shorter than 14 ms or longer than
+    /// 255 units, after rounding to the nearest unit.
+    pub(crate) fn blink_time(blink_ms: u32) -> Option<u8> {
+        let count = blink_ms.saturating_add(7) / 14;
+        if count == 0 {
+            return None;
+        }
+
+        count.try_into().ok()
+    }
+}
+
+/// The condition that raises a GPIO interrupt
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InterruptTrigger {
+    /// While the pin is high
+    HighLevel,
+    /// While the pin is low
+    LowLevel,
+    /// When the pin goes from low to high
+    RisingEdge,
+    /// When the pin goes from high to low
+    FallingEdge,
+    /// When the pin changes
+    BothEdges,
+}
+
+/// A DW3000 GPIO
+///
+/// `N` is the GPIO number, from 0 to 8.
+pub struct Gpio<SPI, const N: u8, MODE> {
+    ll: ll::DW3000<SPI>,
+    _mode: PhantomData<MODE>,
+}
+
+/// GPIO0, also RXOKLED
+pub type Gpio0<SPI, MODE = Unconfigured> = Gpio<SPI, 0, MODE>;
+/// GPIO1, also SFDLED
+pub type Gpio1<SPI, MODE = Unconfigured> = Gpio<SPI, 1, MODE>;
+/// GPIO2, also RXLED
+pub type Gpio2<SPI, MODE = Unconfigured> = Gpio<SPI, 2, MODE>;
+/// GPIO3, also TXLED
+pub type Gpio3<SPI, MODE = Unconfigured> = Gpio<SPI, 3, MODE>;
+/// GPIO4, also EXTPA
+pub type Gpio4<SPI, MODE = Unconfigured> = Gpio<SPI, 4, MODE>;
+/// GPIO5, also EXTTXE
+pub type Gpio5<SPI, MODE = Unconfigured> = Gpio<SPI, 5, MODE>;
+/// GPIO6, also EXTRXE
+pub type Gpio6<SPI, MODE = Unconfigured> = Gpio<SPI, 6, MODE>;
+/// GPIO7, also SYNC
+pub type Gpio7<SPI, MODE = Unconfigured> = Gpio<SPI, 7, MODE>;
+/// GPIO8, also IRQ
+///
+/// Using this pin as a GPIO disables the IRQ output.
+pub type Gpio8<SPI, MODE = Unconfigured> = Gpio<SPI, 8, MODE>;
+
+impl<SPI, const N: u8> Gpio<SPI, N, Unconfigured> {
+    /// Creates a handle from an SPI device on the DW3000's bus
+    pub fn new(spi: SPI) -> Self {
+        const { assert!(N <= 8, "the DW3000 has GPIO0 to GPIO8") };
+
+        Gpio {
+            ll: ll::DW3000::new(spi),
+            _mode: PhantomData,
+        }
+    }
+}
+
+impl<SPI, const N: u8, MODE> Gpio<SPI, N, MODE>
+where
+    SPI: spi_type::spi::SpiDevice<u8>,
+{
+    /// Configures the pin as an output, driving `high` or low
+    ///
+    /// The pin must have been switched to GPIO with
+    /// [`DW3000::select_gpio_mode`](crate::DW3000::select_gpio_mode).
+    #[maybe_async_attr]
+    pub async fn into_output(mut self, high: bool) -> Result<Gpio<SPI, N, Output>, Error<SPI>> {
+        // The output is configured before the direction, so the pin doesn't
+        // glitch. Outputs use the full drive strength, which is the reset
+        // value of MGPEN.
+        self.write_output(high).await?;
+        self.ll
+            .gpio_pull_en()
+            .modify(|_, w| match N {
+                0 => w.mgpen0(1),
+                1 => w.mgpen1(1),
+                2 => w.mgpen2(1),
+                3 => w.mgpen3(1),
+                4 => w.mgpen4(1),
+                5 => w.mgpen5(1),
+                6 => w.mgpen6(1),
+                7 => w.mgpen7(1),
+                _ => w.mgpen8(1),
+            })
+            .await?;
+        self.write_direction(0).await?;
+
+        Ok(Gpio {
+            ll: self.ll,
+            _mode: PhantomData,
+        })
+    }
+
+    /// Configures the pin as an input
+    ///
+    /// The pin must have been switched to GPIO with
+    /// [`DW3000::select_gpio_mode`](crate::DW3000::select_gpio_mode).
+    #[maybe_async_attr]
+    pub async fn into_input(mut self) -> Result<Gpio<SPI, N, Input>, Error<SPI>> {
+        self.write_direction(1).await?;
+
+        Ok(Gpio {
+            ll: self.ll,
+            _mode: PhantomData,
+        })
+    }
+
+    /// Returns the SPI device
+    ///
+    /// The pin keeps its configuration.
+    pub fn release(self) -> SPI {
+        self.ll.spi
+    }
+
+    /// Sets the direction, 1 for input and 0 for output
+    #[maybe_async_attr]
+    async fn write_direction(&mut self, direction: u8) -> Result<(), Error<SPI>> {
+        self.ll
+            .gpio_dir()
+            .modify(|_, w| match N {
+                0 => w.gpd0(direction),
+                1 => w.gpd1(direction),
+                2 => w.gpd2(direction),
+                3 => w.gpd3(direction),
+                4 => w.gpd4(direction),
+                5 => w.gpd5(direction),
+                6 => w.gpd6(direction),
+                7 => w.gpd7(direction),
+                _ => w.gpd8(direction),
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    #[maybe_async_attr]
+    async fn write_output(&mut self, high: bool) -> Result<(), Error<SPI>> {
+        let output = high as u8;
+        self.ll
+            .gpio_out()
+            .modify(|_, w| match N {
+                0 => w.gop0(output),
+                1 => w.gop1(output),
+                2 => w.gop2(output),
+                3 => w.gop3(output),
+                4 => w.gop4(output),
+                5 => w.gop5(output),
+                6 => w.gop6(output),
+                7 => w.gop7(output),
+                _ => w.gop8(output),
+            })
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl<SPI, const N: u8> Gpio<SPI, N, Output>
+where
+    SPI: spi_type::spi::SpiDevice<u8>,
+{
+    /// Drives the pin high
+    #[maybe_async_attr]
+    pub async fn set_high(&mut self) -> Result<(), Error<SPI>> {
+        self.write_output(true).await
+    }
+
+    /// Drives the pin low
+    #[maybe_async_attr]
+    pub async fn set_low(&mut self) -> Result<(), Error<SPI>> {
+        self.write_output(false).await
+    }
+
+    /// Returns true if the pin is driven high
+    #[maybe_async_attr]
+    pub async fn is_set_high(&mut self) -> Result<bool, Error<SPI>> {
+        let gpio_out = self.ll.gpio_out().read().await?;
+        let output = match N {
+            0 => gpio_out.gop0(),
+            1 => gpio_out.gop1(),
+            2 => gpio_out.gop2(),
+            3 => gpio_out.gop3(),
+            4 => gpio_out.gop4(),
+            5 => gpio_out.gop5(),
+            6 => gpio_out.gop6(),
+            7 => gpio_out.gop7(),
+            _ => gpio_out.gop8(),
+        };
+
+        Ok(output == 1)
+    }
+
+    /// Returns true if the pin is driven low
+    #[maybe_async_attr]
+    pub async fn is_set_low(&mut self) -> Result<bool, Error<SPI>> {
+        Ok(!self.is_set_high().await?)
+    }
+
+    /// Toggles the pin
+    #[maybe_async_attr]
+    pub async fn toggle(&mut self) -> Result<(), Error<SPI>> {
+        let high = self.is_set_high().await?;
+        self.write_output(!high).await
+    }
+}
+
+impl<SPI, const N: u8> Gpio<SPI, N, Input>
+where
+    SPI: spi_type::spi::SpiDevice<u8>,
+{
+    /// Returns true if the pin is high
+    #[maybe_async_attr]
+    pub async fn is_high(&mut self) -> Result<bool, Error<SPI>> {
+        let gpio_raw = self.ll.gpio_raw().read().await?;
+        let raw = match N {
+            0 => gpio_raw.grawp0(),
+            1 => gpio_raw.grawp1(),
+            2 => gpio_raw.grawp2(),
+            3 => gpio_raw.grawp3(),
+            4 => gpio_raw.grawp4(),
+            5 => gpio_raw.grawp5(),
+            6 => gpio_raw.grawp6(),
+            7 => gpio_raw.grawp7(),
+            _ => gpio_raw.grawp8(),
+        };
+
+        Ok(raw == 1)
+    }
+
+    /// Returns true if the pin is low
+    #[maybe_async_attr]
+    pub async fn is_low(&mut self) -> Result<bool, Error<SPI>> {
+        Ok(!self.is_high().await?)
+    }
+}
+
+impl<SPI, const N: u8> Gpio<SPI, N, Input>
+where
+    SPI: spi_type::spi::SpiDevice<u8>,
+{
+    /// Enables the interrupt of this pin
+    ///
+    /// The interrupt is latched and raises the
+    /// [`Event::GPIO`](crate::Event::GPIO) event. The IRQ pin of the DW3000
+    /// only goes high for it once the radio enabled the event with
+    /// [`DW3000::enable_gpio_interrupts`](crate::DW3000::enable_gpio_interrupts).
+    /// With `debounce`, the pin has to be stable for a few periods of the
+    /// kilohertz clock before the interrupt triggers.
+    #[maybe_async_attr]
+    pub async fn enable_interrupt(
+        &mut self,
+        trigger: InterruptTrigger,
+        debounce: bool,
+    ) -> Result<(), Error<SPI>> {
+        // Sense: 0 for high or rising, 1 for low or falling
+        let sense = matches!(
+            trigger,
+            InterruptTrigger::LowLevel | InterruptTrigger::FallingEdge
+        ) as u8;
+        // Mode: 0 for level, 1 for edge
+        let edge = !matches!(
+            trigger,
+            InterruptTrigger::HighLevel | InterruptTrigger::LowLevel
+        ) as u8;
+        let both_edges = (trigger == InterruptTrigger::BothEdges) as u8;
+        let debounce = debounce as u8;
+
+        self.ll
+            .gpio_isen()
+            .modify(|_, w| match N {
+                0 => w.gisen0(sense),
+                1 => w.gisen1(sense),
+                2 => w.gisen2(sense),
+                3 => w.gisen3(sense),
+                4 => w.gisen4(sense),
+                5 => w.gisen5(sense),
+                6 => w.gisen6(sense),
+                7 => w.gisen7(sense),
+                _ => w.gisen8(sense),
+            })
+            .await?;
+        self.ll
+            .gpio_imode()
+            .modify(|_, w| match N {
+                0 => w.gimod0(edge),
+                1 => w.gimod1(edge),
+                2 => w.gimod2(edge),
+                3 => w.gimod3(edge),
+                4 => w.gimod4(edge),
+                5 => w.gimod5(edge),
+                6 => w.gimod6(edge),
+                7 => w.gimod7(edge),
+                _ => w.gimod8(edge),
+            })
+            .await?;
+        self.ll
+            .gpio_ibes()
+            .modify(|_, w| match N {
+                0 => w.gibes0(both_edges),
+                1 => w.gibes1(both_edges),
+                2 => w.gibes2(both_edges),
+                3 => w.gibes3(both_edges),
+                4 => w.gibes4(both_edges),
+                5 => w.gibes5(both_edges),
+                6 => w.gibes6(both_edges),
+                7 => w.gibes7(both_edges),
+                _ => w.gibes8(both_edges),
+            })
+            .await?;
+        self.ll
+            .gpio_idbe()
+            .modify(|_, w| match N {
+                0 => w.gidbe0(debounce),
+                1 => w.gidbe1(debounce),
+                2 => w.gidbe2(debounce),
+                3 => w.gidbe3(debounce),
+                4 => w.gidbe4(debounce),
+                5 => w.gidbe5(debounce),
+                6 => w.gidbe6(debounce),
+                7 => w.gidbe7(debounce),
+                _ => w.gidbe8(debounce),
+            })
+            .await?;
+
+        // Don't report an edge from before the interrupt was configured
+        self.clear_interrupt().await?;
+
+        self.ll
+            .gpio_irqe()
+            .modify(|_, w| match N {
+                0 => w.girqe0(1),
+                1 => w.girqe1(1),
+                2 => w.girqe2(1),
+                3 => w.girqe3(1),
+                4 => w.girqe4(1),
+                5 => w.girqe5(1),
+                6 => w.girqe6(1),
+                7 => w.girqe7(1),
+                _ => w.girqe8(1),
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Disables the interrupt of this pin
+    ///
+    /// The `GPIO` event stays enabled in the interrupt mask of the radio, as
+    /// other pins might use it.
+    #[maybe_async_attr]
+    pub async fn disable_interrupt(&mut self) -> Result<(), Error<SPI>> {
+        self.ll
+            .gpio_irqe()
+            .modify(|_, w| match N {
+                0 => w.girqe0(0),
+                1 => w.girqe1(0),
+                2 => w.girqe2(0),
+                3 => w.girqe3(0),
+                4 => w.girqe4(0),
+                5 => w.girqe5(0),
+                6 => w.girqe6(0),
+                7 => w.girqe7(0),
+                _ => w.girqe8(0),
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Returns true if the interrupt of this pin has triggered
+    #[maybe_async_attr]
+    pub async fn is_interrupt_pending(&mut self) -> Result<bool, Error<SPI>> {
+        let gpio_ists = self.ll.gpio_ists().read().await?;
+        let status = match N {
+            0 => gpio_ists.gists0(),
+            1 => gpio_ists.gists1(),
+            2 => gpio_ists.gists2(),
+            3 => gpio_ists.gists3(),
+            4 => gpio_ists.gists4(),
+            5 => gpio_ists.gists5(),
+            6 => gpio_ists.gists6(),
+            7 => gpio_ists.gists7(),
+            _ => gpio_ists.gists8(),
+        };
+
+        Ok(status == 1)
+    }
+
+    /// Clears the interrupt latch of this pin and the `GPIO` event
+    ///
+    /// Level interrupts trigger again right away while the level persists.
+    #[maybe_async_attr]
+    pub async fn clear_interrupt(&mut self) -> Result<(), Error<SPI>> {
+        // The latch is cleared by writing a 1
+        self.ll
+            .gpio_iclr()
+            .write(|w| match N {
+                0 => w.giclr0(1),
+                1 => w.giclr1(1),
+                2 => w.giclr2(1),
+                3 => w.giclr3(1),
+                4 => w.giclr4(1),
+                5 => w.giclr5(1),
+                6 => w.giclr6(1),
+                7 => w.giclr7(1),
+                _ => w.giclr8(1),
+            })
+            .await?;
+        self.ll.sys_status().write(|w| w.gpioirq(0b1)).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "async"))]
+mod digital {
+    use embedded_hal::digital::{self, ErrorKind, ErrorType};
+
+    use super::{Gpio, Input, Output};
+    use crate::Error;
+
+    impl<SPI> digital::Error for Error<SPI>
+    where
+        SPI: embedded_hal::spi::ErrorType,
+    {
+        fn kind(&self) -> ErrorKind {
+            ErrorKind::Other
+        }
+    }
+
+    impl<SPI, const N: u8, MODE> ErrorType for Gpio<SPI, N, MODE>
+    where
+        SPI: embedded_hal::spi::SpiDevice<u8>,
+    {
+        type Error = Error<SPI>;
+    }
+
+    impl<SPI, const N: u8> digital::OutputPin for Gpio<SPI, N, Output>
+    where
+        SPI: embedded_hal::spi::SpiDevice<u8>,
+    {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            Gpio::set_low(self)
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            Gpio::set_high(self)
+        }
+    }
+
+    impl<SPI, const N: u8> digital::StatefulOutputPin for Gpio<SPI, N, Output>
+    where
+        SPI: embedded_hal::spi::SpiDevice<u8>,
+    {
+        fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+            Gpio::is_set_high(self)
+        }
+
+        fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+            Gpio::is_set_low(self)
+        }
+
+        fn toggle(&mut self) -> Result<(), Self::Error> {
+            Gpio::toggle(self)
+        }
+    }
+
+    impl<SPI, const N: u8> digital::InputPin for Gpio<SPI, N, Input>
+    where
+        SPI: embedded_hal::spi::SpiDevice<u8>,
+    {
+        fn is_high(&mut self) -> Result<bool, Self::Error> {
+            Gpio::is_high(self)
+        }
+
+        fn is_low(&mut self) -> Result<bool, Self::Error> {
+            Gpio::is_low(self)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use embedded_hal_mock::eh1::spi::Mock as SpiMock;
+
+    use crate::test_utils::{read, write};
+
+    #[test]
+    fn led_blink_time() {
+        assert_eq!(Led::blink_time(0), None);
+        assert_eq!(Led::blink_time(6), None);
+        assert_eq!(Led::blink_time(7), Some(1));
+        assert_eq!(Led::blink_time(448), Some(32));
+        assert_eq!(Led::blink_time(255 * 14), Some(255));
+        assert_eq!(Led::blink_time(256 * 14), None);
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(all(feature = "async"), tokio::test))]
+    async fn test_output() {
+        let expectations = [
+            // GPIO clocks
+            read(0x11, 0x04, &[0, 0, 0, 0]),
+            write(0x11, 0x04, &[0, 0, 0x8D, 0]),
+            // GPIO8 defaults to IRQ
+            read(0x05, 0x00, &[0, 0, 0, 0]),
+            write(0x05, 0x00, &[0, 0, 0, 0b1]),
+            // Output, drive strength and direction
+            read(0x05, 0x0C, &[0x00, 0x00]),
+            write(0x05, 0x0C, &[0x00, 0x01]),
+            read(0x05, 0x04, &[0x00, 0x00]),
+            write(0x05, 0x04, &[0x00, 0x01]),
+            read(0x05, 0x08, &[0xFF, 0x01]),
+            write(0x05, 0x08, &[0xFF, 0x00]),
+            // Toggle
+            read(0x05, 0x0C, &[0x00, 0x01]),
+            read(0x05, 0x0C, &[0x00, 0x01]),
+            write(0x05, 0x0C, &[0x00, 0x00]),
+        ]
+        .concat();
+        let mut spi = SpiMock::new(&expectations);
+
+        let mut radio = crate::DW3000::new_ready(spi.clone());
+        let gpio: Gpio8<_> = Gpio::new(spi.clone());
+        radio.select_gpio_mode(&gpio).await.unwrap();
+        let mut gpio = gpio.into_output(true).await.unwrap();
+        gpio.toggle().await.unwrap();
+
+        spi.done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(all(feature = "async"), tokio::test))]
+    async fn test_input() {
+        let expectations = [
+            // Direction
+            read(0x05, 0x08, &[0x00, 0x00]),
+            write(0x05, 0x08, &[0x20, 0x00]),
+            // Level
+            read(0x05, 0x2C, &[0x20, 0x00]),
+        ]
+        .concat();
+        let spi = SpiMock::new(&expectations);
+
+        let gpio: Gpio5<_> = Gpio::new(spi);
+        let mut gpio = gpio.into_input().await.unwrap();
+        let high = gpio.is_high().await.unwrap();
+        assert!(high);
+
+        gpio.release().done();
+    }
+
+    #[cfg(not(feature = "async"))]
+    #[test]
+    fn test_embedded_hal() {
+        use embedded_hal::digital::{OutputPin, StatefulOutputPin};
+
+        fn turn_on<P: OutputPin + StatefulOutputPin>(pin: &mut P) -> bool {
+            pin.set_high().unwrap();
+            pin.is_set_high().unwrap()
+        }
+
+        let expectations = [
+            // Output, drive strength and direction
+            read(0x05, 0x0C, &[0x00, 0x00]),
+            write(0x05, 0x0C, &[0x00, 0x00]),
+            read(0x05, 0x04, &[0x00, 0x00]),
+            write(0x05, 0x04, &[0x08, 0x00]),
+            read(0x05, 0x08, &[0xFF, 0x01]),
+            write(0x05, 0x08, &[0xF7, 0x01]),
+            // Through the traits
+            read(0x05, 0x0C, &[0x00, 0x00]),
+            write(0x05, 0x0C, &[0x08, 0x00]),
+            read(0x05, 0x0C, &[0x08, 0x00]),
+        ]
+        .concat();
+        let spi = SpiMock::new(&expectations);
+
+        let gpio: Gpio3<_> = Gpio::new(spi);
+        let mut gpio = gpio.into_output(false).unwrap();
+        assert!(turn_on(&mut gpio));
+
+        gpio.release().done();
+    }
+}
@@ -1,8 +1,9 @@
 use super::Awake;
 use crate::{
+    configs::ReceiveMode,
     fast_command, ll,
     time::{Duration, Instant},
-    Error, DW3000,
+    Config, Error, Event, DW3000,
 };
 
 use smoltcp::wire::{Ieee802154Address, Ieee802154Pan};
@@ -56,6 +57,18 @@ where
         Ok(Instant((sys_time as u64) << 8))
     }
 
+    /// Returns the clock offset of the sender of the last received frame
+    ///
+    /// The value is in units of 2^-26 (about 0.015 ppm). A positive value
+    /// means the local clock runs slower than the sender's clock.
+    #[maybe_async_attr]
+    pub async fn get_clock_offset(&mut self) -> Result<i16, Error<SPI>> {
+        let coe_ppm = self.ll.cia_diag_0().read().await?.coe_ppm();
+
+        // Sign-extend the 13-bit value
+        Ok(((coe_ppm << 3) as i16) >> 3)
+    }
+
     /// Returns the state of the DW3000
     #[maybe_async_attr]
     pub async fn state(&mut self) -> Result<u8, Error<SPI>> {
@@ -108,6 +121,273 @@ where
         Ok(())
     }
 
+    /// Sets the events that drive the IRQ pin
+    ///
+    /// Overwrites the previous mask, including the ones set by
+    /// [`DW3000::enable_tx_interrupts`] and [`DW3000::enable_rx_interrupts`].
+    #[maybe_async_attr]
+    pub async fn set_interrupt_mask(&mut self, events: Event) -> Result<(), Error<SPI>> {
+        self.ll
+            .sys_enable()
+            .write(|w| {
+                let buffer = <ll::SYS_ENABLE as ll::Writable>::buffer(w);
+                let len = buffer.len();
+                events.to_register(&mut buffer[len - 6..]);
+                w
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Returns the events that drive the IRQ pin
+    #[maybe_async_attr]
+    pub async fn interrupt_mask(&mut self) -> Result<Event, Error<SPI>> {
+        let mut sys_enable = self.ll.sys_enable().read().await?;
+        let buffer = <ll::SYS_ENABLE as ll::Readable>::buffer(&mut sys_enable);
+
+        Ok(Event::from_register(&buffer[buffer.len() - 6..]))
+    }
+
+    /// Returns the events that are currently set in `SYS_STATUS`
+    ///
+    /// This includes events that are not part of the interrupt mask.
+    #[maybe_async_attr]
+    pub async fn pending_events(&mut self) -> Result<Event, Error<SPI>> {
+        let mut sys_status = self.ll.sys_status().read().await?;
+        let buffer = <ll::SYS_STATUS as ll::Readable>::buffer(&mut sys_status);
+
+        Ok(Event::from_register(&buffer[buffer.len() - 6..]))
+    }
+
+    /// Clears `events` in `SYS_STATUS`
+    ///
+    /// Other events are left untouched. The IRQ pin goes low once no event of
+    /// the interrupt mask is set.
+    #[maybe_async_attr]
+    pub async fn clear_events(&mut self, events: Event) -> Result<(), Error<SPI>> {
+        // The events are cleared by writing a 1
+        self.ll
+            .sys_status()
+            .write(|w| {
+                let buffer = <ll::SYS_STATUS as ll::Writable>::buffer(w);
+                let len = buffer.len();
+                events.to_register(&mut buffer[len - 6..]);
+                w
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Configures the receive timeouts from `config`
+    ///
+    /// Needs to happen before the receiver is enabled, either directly or at
+    /// the end of a transmission.
+    #[maybe_async_attr]
+    pub(super) async fn set_rx_timeouts(&mut self, config: &Config) -> Result<(), Error<SPI>> {
+        match config.rx_frame_wait_timeout {
+            Some(timeout) => {
+                // RX_FWTO counts in units of 512 / 499.2 MHz, which is 65536
+                // time units
+                let fwto = timeout.value().div_ceil(1 << 16);
+                if fwto == 0 || fwto > 0xFF_FFFF {
+                    return Err(Error::InvalidConfiguration);
+                }
+
+                self.ll.rx_fwto().write(|w| w.value(fwto as u32)).await?;
+                self.ll.sys_cfg().modify(|_, w| w.rxwtoe(0b1)).await?;
+            }
+            None => self.ll.sys_cfg().modify(|_, w| w.rxwtoe(0b0)).await?,
+        }
+
+        // A value of 0 disables the preamble detection timeout
+        let pre_toc = match config.rx_preamble_timeout {
+            Some(0) => return Err(Error::InvalidConfiguration),
+            Some(timeout) => timeout,
+            None => 0,
+        };
+        self.ll.pre_toc().write(|w| w.value(pre_toc)).await?;
+
+        Ok(())
+    }
+
+    /// Configures the SNIFF mode of the receiver from `config`
+    #[maybe_async_attr]
+    pub(super) async fn set_receive_mode(&mut self, config: &Config) -> Result<(), Error<SPI>> {
+        let (sniff_on, sniff_off) = match config.receive_mode {
+            ReceiveMode::Continuous => (0, 0),
+            ReceiveMode::Sniff { on_pacs, off_us } => {
+                // A zero ON time disables the SNIFF mode, and the field is
+                // only 4 bits wide
+                if on_pacs == 0 || on_pacs > 15 {
+                    return Err(Error::InvalidConfiguration);
+                }
+                (on_pacs, off_us)
+            }
+        };
+
+        self.ll
+            .rx_sniff()
+            .write(|w| w.sniff_on(sniff_on).sniff_off(sniff_off))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Registers a short address that has data pending
+    ///
+    /// When the DW3000 auto-acknowledges a MAC data request from this address,
+    /// it sets the frame pending bit in the ACK. This needs frame filtering
+    /// and auto-acks to be enabled. Up to four addresses can be registered at
+    /// a time, registering an address twice has no effect.
+    #[maybe_async_attr]
+    pub async fn add_pending_address(&mut self, addr: Ieee802154Address) -> Result<(), Error<SPI>> {
+        let Ieee802154Address::Short(short_addr) = addr else {
+            return Err(Error::InvalidConfiguration);
+        };
+        let short_addr = u16::from_be_bytes(short_addr);
+
+        let slots = self.pending_address_slots().await?;
+        if slots.contains(&Some(short_addr)) {
+            return Ok(());
+        }
+        let Some(slot) = slots.iter().position(Option::is_none) else {
+            return Err(Error::PendingAddressTableFull);
+        };
+
+        match slot {
+            0 => {
+                self.ll
+                    .le_pend_01()
+                    .modify(|_, w| w.le_addr0(short_addr))
+                    .await?
+            }
+            1 => {
+                self.ll
+                    .le_pend_01()
+                    .modify(|_, w| w.le_addr1(short_addr))
+                    .await?
+            }
+            2 => {
+                self.ll
+                    .le_pend_23()
+                    .modify(|_, w| w.le_addr2(short_addr))
+                    .await?
+            }
+            _ => {
+                self.ll
+                    .le_pend_23()
+                    .modify(|_, w| w.le_addr3(short_addr))
+                    .await?
+            }
+        }
+        self.set_pending_address_slot(slot, true).await
+    }
+
+    /// Unregisters a short address that had data pending
+    ///
+    /// Does nothing if the address was not registered.
+    #[maybe_async_attr]
+    pub async fn remove_pending_address(
+        &mut self,
+        addr: Ieee802154Address,
+    ) -> Result<(), Error<SPI>> {
+        let Ieee802154Address::Short(short_addr) = addr else {
+            return Err(Error::InvalidConfiguration);
+        };
+        let short_addr = u16::from_be_bytes(short_addr);
+
+        let slots = self.pending_address_slots().await?;
+        for (slot, _) in slots
+            .iter()
+            .enumerate()
+            .filter(|(_, addr)| **addr == Some(short_addr))
+        {
+            self.set_pending_address_slot(slot, false).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Unregisters all short addresses that had data pending
+    #[maybe_async_attr]
+    pub async fn clear_pending_addresses(&mut self) -> Result<(), Error<SPI>> {
+        self.ll
+            .ff_cfg()
+            .modify(|_, w| w.le0_pend(0b0).le1_pend(0b0).le2_pend(0b0).le3_pend(0b0))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Sets the frame pending bit in auto-acks to all data requests that use a
+    /// short source address, regardless of the registered addresses
+    #[maybe_async_attr]
+    pub async fn set_pending_data_for_short_addresses(
+        &mut self,
+        enabled: bool,
+    ) -> Result<(), Error<SPI>> {
+        self.ll
+            .ff_cfg()
+            .modify(|_, w| w.ssadrap(enabled as u8))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Sets the frame pending bit in auto-acks to all data requests that use a
+    /// long source address
+    #[maybe_async_attr]
+    pub async fn set_pending_data_for_long_addresses(
+        &mut self,
+        enabled: bool,
+    ) -> Result<(), Error<SPI>> {
+        self.ll
+            .ff_cfg()
+            .modify(|_, w| w.lsadrape(enabled as u8))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Returns the registered pending addresses, `None` for free slots
+    #[maybe_async_attr]
+    async fn pending_address_slots(&mut self) -> Result<[Option<u16>; 4], Error<SPI>> {
+        let ff_cfg = self.ll.ff_cfg().read().await?;
+        let le_pend_01 = self.ll.le_pend_01().read().await?;
+        let le_pend_23 = self.ll.le_pend_23().read().await?;
+
+        let slots = [
+            (ff_cfg.le0_pend(), le_pend_01.le_addr0()),
+            (ff_cfg.le1_pend(), le_pend_01.le_addr1()),
+            (ff_cfg.le2_pend(), le_pend_23.le_addr2()),
+            (ff_cfg.le3_pend(), le_pend_23.le_addr3()),
+        ];
+
+        Ok(slots.map(|(pending, addr)| (pending == 0b1).then_some(addr)))
+    }
+
+    #[maybe_async_attr]
+    async fn set_pending_address_slot(
+        &mut self,
+        slot: usize,
+        pending: bool,
+    ) -> Result<(), Error<SPI>> {
+        let pending = pending as u8;
+        self.ll
+            .ff_cfg()
+            .modify(|_, w| match slot {
+                0 => w.le0_pend(pending),
+                1 => w.le1_pend(pending),
+                2 => w.le2_pend(pending),
+                _ => w.le3_pend(pending),
+            })
+            .await?;
+
+        Ok(())
+    }
+
     /// Use fast command ll in hl
     #[maybe_async_attr]
     pub async fn fast_cmd(&mut self, fc: fast_command::FastCommand) -> Result<(), Error<SPI>> {
@@ -149,4 +429,40 @@ mod test {
 
         spi.done();
     }
+
+    #[maybe_async::test(not(feature = "async"), async(all(feature = "async"), tokio::test))]
+    async fn test_events() {
+        let spi = SpiMock::new(&[
+            SpiTransaction::transaction_start(),
+            SpiTransaction::transfer_in_place(
+                vec![0x41, 0x10, 0, 0, 0, 0, 0, 0],
+                vec![0x41, 0x10, 0x81, 0x40, 0x02, 0x00, 0x00, 0x10],
+            ),
+            SpiTransaction::transaction_end(),
+            SpiTransaction::transaction_start(),
+            SpiTransaction::write_vec(vec![0xC1, 0x10, 0x80, 0x40, 0x00, 0x00, 0x00, 0x10]),
+            SpiTransaction::transaction_end(),
+        ]);
+
+        let mut dw3000 = DW3000::new(spi);
+
+        // The IRQS bit is not an event
+        let events = dw3000.pending_events().await.unwrap();
+        assert_eq!(
+            events,
+            Event::TX_FRAME_SENT
+                | Event::RX_FCS_GOOD
+                | Event::RX_FRAME_WAIT_TIMEOUT
+                | Event::CCA_FAIL
+        );
+
+        dw3000
+            .clear_events(events - Event::RX_TIMEOUTS)
+            .await
+            .unwrap();
+
+        let mut spi = dw3000.ll.spi;
+
+        spi.done();
+    }
 }
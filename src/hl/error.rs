@@ -98,6 +98,43 @@ where
 
     /// The transmission has not yet finished
     TxNotFinishedYet,
+
+    /// The frame doesn't fit into the 127 bytes of an IEEE 802.15.4 frame
+    FrameTooLong,
+
+    /// A preamble was detected during the clear channel assessment, so the
+    /// frame was not sent
+    ChannelBusy,
+
+    /// All four data pending address slots are in use
+    PendingAddressTableFull,
+
+    /// Error occurred while waiting for the IRQ pin
+    Irq,
+
+    /// The OTP address already holds a different value
+    OtpAlreadyProgrammed,
+
+    /// The OTP programming voltage is not present
+    OtpVoltage,
+
+    /// The value read back from the OTP memory differs from the programmed one
+    OtpVerificationFailed,
+
+    /// The OTP memory didn't report the end of programming in time
+    OtpTimeout,
+
+    /// The DW3000 is not in IDLE_RC, which OTP programming requires
+    OtpNotIdleRc,
+
+    /// The SAR conversion did not finish
+    SarTimeout,
+
+    /// The OTP memory holds no calibration reference for the measurement
+    NotCalibrated,
+
+    /// The crystal trim ran out of range or didn't converge
+    XtalTuningFailed,
 }
 
 impl<SPI> From<ll::Error<SPI>> for Error<SPI>
@@ -156,6 +193,18 @@ where
             Error::PGFCalibrationFailed => write!(f, "PGFCalibrationFailed"),
             Error::WrongTxContinuation => write!(f, "WrongTxContinuation"),
             Error::TxNotFinishedYet => write!(f, "TxNotFinishedYet"),
+            Error::FrameTooLong => write!(f, "FrameTooLong"),
+            Error::ChannelBusy => write!(f, "ChannelBusy"),
+            Error::PendingAddressTableFull => write!(f, "PendingAddressTableFull"),
+            Error::Irq => write!(f, "Irq"),
+            Error::OtpAlreadyProgrammed => write!(f, "OtpAlreadyProgrammed"),
+            Error::OtpVoltage => write!(f, "OtpVoltage"),
+            Error::OtpVerificationFailed => write!(f, "OtpVerificationFailed"),
+            Error::OtpTimeout => write!(f, "OtpTimeout"),
+            Error::OtpNotIdleRc => write!(f, "OtpNotIdleRc"),
+            Error::SarTimeout => write!(f, "SarTimeout"),
+            Error::NotCalibrated => write!(f, "NotCalibrated"),
+            Error::XtalTuningFailed => write!(f, "XtalTuningFailed"),
         }
     }
 }
@@ -197,6 +246,18 @@ where
             Error::PGFCalibrationFailed => defmt::write!(f, "PGFCalibrationFailed"),
             Error::WrongTxContinuation => defmt::write!(f, "WrongTxContinuation"),
             Error::TxNotFinishedYet => defmt::write!(f, "TxNotFinishedYet"),
+            Error::FrameTooLong => defmt::write!(f, "FrameTooLong"),
+            Error::ChannelBusy => defmt::write!(f, "ChannelBusy"),
+            Error::PendingAddressTableFull => defmt::write!(f, "PendingAddressTableFull"),
+            Error::Irq => defmt::write!(f, "Irq"),
+            Error::OtpAlreadyProgrammed => defmt::write!(f, "OtpAlreadyProgrammed"),
+            Error::OtpVoltage => defmt::write!(f, "OtpVoltage"),
+            Error::OtpVerificationFailed => defmt::write!(f, "OtpVerificationFailed"),
+            Error::OtpTimeout => defmt::write!(f, "OtpTimeout"),
+            Error::OtpNotIdleRc => defmt::write!(f, "OtpNotIdleRc"),
+            Error::SarTimeout => defmt::write!(f, "SarTimeout"),
+            Error::NotCalibrated => defmt::write!(f, "NotCalibrated"),
+            Error::XtalTuningFailed => defmt::write!(f, "XtalTuningFailed"),
         }
     }
 }
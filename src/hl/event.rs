@@ -0,0 +1,125 @@
+use bitflags::bitflags;
+
+bitflags! {
+    /// A set of DW3000 system events
+    ///
+    /// Each flag has the bit position of its event in the `SYS_STATUS` and
+    /// `SYS_ENABLE` registers, so a set can be used both as an interrupt mask
+    /// (see [`DW3000::set_interrupt_mask`](crate::DW3000::set_interrupt_mask))
+    /// and to read and clear pending events (see
+    /// [`DW3000::pending_events`](crate::DW3000::pending_events)).
+    #[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+    pub struct Event: u64 {
+        /// Clock PLL lock
+        const CLOCK_PLL_LOCK = 1 << 1;
+        /// SPI CRC error
+        const SPI_CRC_ERROR = 1 << 2;
+        /// Automatic acknowledge trigger
+        const AUTO_ACK_TRIGGER = 1 << 3;
+        /// TX frame begins
+        const TX_FRAME_BEGINS = 1 << 4;
+        /// TX preamble sent
+        const TX_PREAMBLE_SENT = 1 << 5;
+        /// TX PHY header sent
+        const TX_PHY_HEADER_SENT = 1 << 6;
+        /// TX frame sent
+        const TX_FRAME_SENT = 1 << 7;
+        /// RX preamble detected
+        const RX_PREAMBLE_DETECTED = 1 << 8;
+        /// RX SFD detected
+        const RX_SFD_DETECTED = 1 << 9;
+        /// CIA processing done
+        const CIA_DONE = 1 << 10;
+        /// RX PHY header detected
+        const RX_PHY_HEADER_DETECTED = 1 << 11;
+        /// RX PHY header error
+        const RX_PHY_HEADER_ERROR = 1 << 12;
+        /// RX data frame ready
+        const RX_FRAME_READY = 1 << 13;
+        /// RX FCS good
+        const RX_FCS_GOOD = 1 << 14;
+        /// RX FCS error
+        const RX_FCS_ERROR = 1 << 15;
+        /// RX Reed Solomon frame sync loss
+        const RX_REED_SOLOMON_ERROR = 1 << 16;
+        /// RX frame wait timeout
+        const RX_FRAME_WAIT_TIMEOUT = 1 << 17;
+        /// CIA (leading edge detection) processing error
+        const CIA_ERROR = 1 << 18;
+        /// Low voltage warning
+        const VOLTAGE_WARNING = 1 << 19;
+        /// RX overrun
+        const RX_OVERRUN = 1 << 20;
+        /// RX preamble detection timeout
+        const RX_PREAMBLE_TIMEOUT = 1 << 21;
+        /// SPI ready for host access
+        const SPI_READY = 1 << 23;
+        /// IDLE RC reached
+        const RC_INIT = 1 << 24;
+        /// PLL losing lock
+        const PLL_LOSING_LOCK = 1 << 25;
+        /// RX SFD timeout
+        const RX_SFD_TIMEOUT = 1 << 26;
+        /// Half period delay warning, the delayed TX or RX started late
+        const HALF_PERIOD_DELAY_WARNING = 1 << 27;
+        /// Scrambled timestamp sequence (STS) error
+        const STS_ERROR = 1 << 28;
+        /// Frame rejected by the automatic frame filter
+        const FRAME_FILTER_REJECTION = 1 << 29;
+        /// RX preamble rejection
+        const RX_PREAMBLE_REJECTION = 1 << 33;
+        /// Voltage or temperature variation detected
+        const VT_DETECTED = 1 << 36;
+        /// GPIO interrupt
+        const GPIO = 1 << 37;
+        /// AES-DMA operation done
+        const AES_DONE = 1 << 38;
+        /// AES-DMA error
+        const AES_ERROR = 1 << 39;
+        /// Command error
+        const COMMAND_ERROR = 1 << 40;
+        /// SPI overflow error
+        const SPI_OVERFLOW = 1 << 41;
+        /// SPI underflow error
+        const SPI_UNDERFLOW = 1 << 42;
+        /// SPI collision error
+        const SPI_COLLISION = 1 << 43;
+        /// Clear channel assessment failed, the frame was not sent
+        const CCA_FAIL = 1 << 44;
+
+        /// The reception errors that [`DW3000::r_wait`](crate::DW3000::r_wait)
+        /// reports
+        const RX_ERRORS = Self::RX_PHY_HEADER_ERROR.bits()
+            | Self::RX_FCS_ERROR.bits()
+            | Self::RX_REED_SOLOMON_ERROR.bits()
+            | Self::RX_SFD_TIMEOUT.bits()
+            | Self::FRAME_FILTER_REJECTION.bits()
+            | Self::RX_OVERRUN.bits();
+        /// The receiver timeouts
+        const RX_TIMEOUTS = Self::RX_FRAME_WAIT_TIMEOUT.bits()
+            | Self::RX_PREAMBLE_TIMEOUT.bits();
+    }
+}
+
+impl Event {
+    /// Creates a set from the 48-bit `SYS_STATUS` or `SYS_ENABLE` register
+    /// data, ignoring reserved bits
+    pub(crate) fn from_register(bytes: &[u8]) -> Self {
+        let mut value = [0; 8];
+        value[..6].copy_from_slice(&bytes[..6]);
+
+        Event::from_bits_truncate(u64::from_le_bytes(value))
+    }
+
+    /// Writes the set to the 48-bit `SYS_STATUS` or `SYS_ENABLE` register data
+    pub(crate) fn to_register(self, bytes: &mut [u8]) {
+        bytes[..6].copy_from_slice(&self.bits().to_le_bytes()[..6]);
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Event {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "Event({=u64:#x})", self.bits())
+    }
+}
@@ -14,27 +14,39 @@ use core::{fmt, num::Wrapping};
 #[allow(unused_imports)]
 pub use awake::*;
 pub use error::*;
+pub use event::*;
+pub use otp::*;
 pub use ready::*;
 #[allow(unused_imports)]
 pub use receiving::*;
+pub use reset::RadioSettings;
 #[allow(unused_imports)]
 pub use sending::*;
 #[allow(unused_imports)]
 pub use sleeping::*;
 pub use state_impls::*;
 #[allow(unused_imports)]
+pub use test_mode::*;
+#[allow(unused_imports)]
 pub use uninitialized::*;
+pub use xtal::XTAL_TRIM_MAX;
 
 use crate::ll;
 
 mod awake;
 mod error;
+mod event;
+mod otp;
 mod ready;
 mod receiving;
+mod reset;
+mod sar;
 mod sending;
 mod sleeping;
 mod state_impls;
+mod test_mode;
 mod uninitialized;
+mod xtal;
 
 /// Entry point to the DW3000 driver API
 #[derive(Copy, Clone)]
@@ -57,3 +69,16 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+impl<SPI> DW3000<SPI, Ready> {
+    /// Creates a driver that is already in the `Ready` state, so tests don't
+    /// have to mock the whole initialization
+    pub(crate) fn new_ready(spi: SPI) -> Self {
+        DW3000 {
+            ll: ll::DW3000::new(spi),
+            seq: Wrapping(0),
+            state: Ready,
+        }
+    }
+}
@@ -0,0 +1,517 @@
+use super::Awake;
+use crate::{ll, maybe_async_attr, spi_type, Error, DW3000};
+
+#[cfg(not(feature = "async"))]
+use embedded_hal::delay::DelayNs;
+#[cfg(feature = "async")]
+use embedded_hal_async::delay::DelayNs;
+
+/// The factory calibration and identification data in the OTP memory
+///
+/// Values that are not programmed read as 0, and are `None` here.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OtpData {
+    /// The EUI-64 (0x00 and 0x01)
+    pub eui: Option<u64>,
+    /// LDO tuning (0x04 and 0x05)
+    pub ldo_tune: Option<u64>,
+    /// The part ID (0x06)
+    pub part_id: u32,
+    /// The lot ID (0x07)
+    pub lot_id: u32,
+    /// The SAR voltage reading at 3.0 V (0x08)
+    pub vbat_ref: Option<u8>,
+    /// The SAR temperature reading at 20 °C (0x09)
+    pub temp_ref: Option<u8>,
+    /// Bias tuning (0x0A, bits 16 to 20)
+    pub bias_tune: Option<u8>,
+    /// The RX and TX antenna delays (0x1C, lower and upper 16 bits)
+    ///
+    /// These are not programmed at the factory. 0x1C is the antenna delay
+    /// word of the DW1000 OTP memory map (DW1000 User Manual, section 6.3.1),
+    /// while the DW3000 User Manual doesn't assign it. It only holds antenna
+    /// delays if they were calibrated and programmed there per unit, see
+    /// [`DW3000::apply_otp_antenna_delay`].
+    pub antenna_delay: Option<(u16, u16)>,
+    /// The crystal trim (0x1E)
+    pub xtal_trim: Option<u8>,
+    /// The OTP revision (0x1F)
+    pub revision: u8,
+    /// The PLL lock code (0x35)
+    pub pll_lock_code: Option<u32>,
+}
+
+/// The OTP address of the antenna delays, see [`OtpData::antenna_delay`]
+const OTP_ANTENNA_DELAY_ADDR: u16 = 0x1C;
+
+/// The number of 32-bit words in the OTP memory
+const OTP_WORDS: u16 = 0x80;
+
+/// The `LDO_HVAUX_TUNE` field of `LDO_TUNE`, which is raised while programming
+const LDO_HVAUX_TUNE: u128 = 0xF << 44;
+
+/// The `PMSC_STATE` of IDLE_RC
+const PMSC_STATE_IDLE_RC: u8 = 0x2;
+
+/// The OTP mode register value that prepares the memory for programming
+const OTP_MR_PROGRAM: u32 = 0x0018;
+
+/// The OTP mode register value that selects fast programming
+const OTP_MR_FAST_PROGRAM: u32 = 0x0025;
+
+/// The OTP mode register value for reading, which is the default
+const OTP_MR_READ: u32 = 0x0000;
+
+/// The time between two polls of `OTP_STAT` for the end of programming
+const OTP_PROG_POLL_US: u32 = 10;
+
+/// How long programming a word may take before [`Error::OtpTimeout`]
+const OTP_PROG_TIMEOUT_US: u32 = 5_000;
+
+impl OtpData {
+    /// The OTP addresses that [`OtpData::from_words`] expects, in order
+    pub const ADDRESSES: [u16; 13] = [
+        0x00, 0x01, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x1C, 0x1E, 0x1F, 0x35,
+    ];
+
+    /// Parses the words read from [`OtpData::ADDRESSES`]
+    pub fn from_words(words: &[u32; 13]) -> Self {
+        let [eui_l, eui_h, ldo_l, ldo_h, part_id, lot_id, vbat, temp, bias, antd, xtal, rev, pll] =
+            *words;
+
+        let non_zero_u8 = |value: u32| Some(value as u8).filter(|&value| value != 0);
+
+        OtpData {
+            eui: Some((eui_h as u64) << 32 | eui_l as u64).filter(|&eui| eui != 0),
+            ldo_tune: Some((ldo_h as u64) << 32 | ldo_l as u64)
+                .filter(|_| ldo_l != 0 && ldo_h != 0),
+            part_id,
+            lot_id,
+            vbat_ref: non_zero_u8(vbat & 0xFF),
+            temp_ref: non_zero_u8(temp & 0xFF),
+            bias_tune: non_zero_u8((bias >> 16) & 0x1F),
+            antenna_delay: Some((antd as u16, (antd >> 16) as u16)).filter(|_| antd != 0),
+            xtal_trim: non_zero_u8(xtal & 0x3F),
+            revision: rev as u8,
+            pll_lock_code: Some(pll).filter(|&pll| pll != 0),
+        }
+    }
+}
+
+impl<SPI, State> DW3000<SPI, State>
+where
+    SPI: spi_type::spi::SpiDevice<u8>,
+    State: Awake,
+{
+    /// Read the OTP memory at the given address
+    #[maybe_async_attr]
+    pub async fn read_otp(&mut self, addr: u16) -> Result<u32, ll::Error<SPI>> {
+        // Set OTP_MAN to 1
+        self.ll.otp_cfg().write(|w| w.otp_man(1)).await?;
+        // Set the 10-bit address
+        self.ll.otp_addr().modify(|_, w| w.otp_addr(addr)).await?;
+        // Set OTP_READ to 1
+        self.ll.otp_cfg().write(|w| w.otp_read(1)).await?;
+        // Read the data (32 bits)
+        let data = self.ll.otp_rdata().read().await?.value();
+        Ok(data)
+    }
+
+    /// Reads and parses the calibration and identification data in the OTP
+    /// memory
+    #[maybe_async_attr]
+    pub async fn read_otp_data(&mut self) -> Result<OtpData, Error<SPI>> {
+        let mut words = [0; 13];
+        for (word, addr) in words.iter_mut().zip(OtpData::ADDRESSES) {
+            *word = self.read_otp(addr).await?;
+        }
+
+        Ok(OtpData::from_words(&words))
+    }
+
+    /// Applies the antenna delays programmed in the OTP memory
+    ///
+    /// [`DW3000::init`] leaves the antenna delays alone, so units that had
+    /// theirs calibrated and programmed at 0x1C (see
+    /// [`OtpData::antenna_delay`]) need to call this after it. Returns the
+    /// RX and TX delays that were applied, or `None` if the word is not
+    /// programmed, in which case the delays are not changed.
+    #[maybe_async_attr]
+    pub async fn apply_otp_antenna_delay(&mut self) -> Result<Option<(u16, u16)>, Error<SPI>> {
+        let word = self.read_otp(OTP_ANTENNA_DELAY_ADDR).await?;
+        if word == 0 {
+            return Ok(None);
+        }
+
+        let (rx_delay, tx_delay) = (word as u16, (word >> 16) as u16);
+        self.ll.cia_conf().modify(|_, w| w.rxantd(rx_delay)).await?;
+        self.ll.tx_antd().write(|w| w.value(tx_delay)).await?;
+
+        Ok(Some((rx_delay, tx_delay)))
+    }
+
+    /// Programs a 32-bit word into the OTP memory and verifies it
+    ///
+    /// OTP bits can only be set, never cleared. Unless `overwrite` is set,
+    /// addresses that already hold data are refused with
+    /// [`Error::OtpAlreadyProgrammed`]. Even then, a value that would need to
+    /// clear bits is always refused. Programming the value that is already
+    /// stored does nothing.
+    ///
+    /// Programming requires the DW3000 to be in IDLE_RC, or
+    /// [`Error::OtpNotIdleRc`] is returned, and the supplies at their nominal
+    /// levels. The internal programming voltage is raised for the duration of
+    /// the write, and [`Error::OtpVoltage`] is returned if it isn't reached.
+    /// [`Error::OtpTimeout`] is returned if the programming doesn't finish
+    /// within 5 ms, `delay_ns` is used to wait between the status polls.
+    /// The word is read back afterwards, and [`Error::OtpVerificationFailed`]
+    /// is returned if it doesn't match.
+    #[maybe_async_attr]
+    pub async fn program_otp<DELAY>(
+        &mut self,
+        addr: u16,
+        value: u32,
+        overwrite: bool,
+        mut delay_ns: DELAY,
+    ) -> Result<(), Error<SPI>>
+    where
+        DELAY: DelayNs,
+    {
+        if addr >= OTP_WORDS {
+            return Err(Error::InvalidConfiguration);
+        }
+
+        let current = self.read_otp(addr).await?;
+        if current == value {
+            return Ok(());
+        }
+        if current != 0 && (!overwrite || current & !value != 0) {
+            return Err(Error::OtpAlreadyProgrammed);
+        }
+        if self.state().await? != PMSC_STATE_IDLE_RC {
+            return Err(Error::OtpNotIdleRc);
+        }
+
+        // Raise the programming voltage
+        let ldo_tune = self.ll.ldo_tune().read().await?.value();
+        self.ll
+            .ldo_tune()
+            .write(|w| w.value(ldo_tune | LDO_HVAUX_TUNE))
+            .await?;
+
+        let result = self.write_otp_word(addr, value, &mut delay_ns).await;
+
+        self.ll.ldo_tune().write(|w| w.value(ldo_tune)).await?;
+        result?;
+
+        if self.read_otp(addr).await? != value {
+            return Err(Error::OtpVerificationFailed);
+        }
+
+        Ok(())
+    }
+
+    /// Writes a word to the OTP memory, following the sequence of the user
+    /// manual
+    #[maybe_async_attr]
+    async fn write_otp_word<DELAY>(
+        &mut self,
+        addr: u16,
+        value: u32,
+        delay_ns: &mut DELAY,
+    ) -> Result<(), Error<SPI>>
+    where
+        DELAY: DelayNs,
+    {
+        if self.ll.otp_stat().read().await?.otp_vpp_ok() == 0 {
+            return Err(Error::OtpVoltage);
+        }
+
+        self.write_otp_mode(OTP_MR_PROGRAM).await?;
+        self.write_otp_mode(OTP_MR_FAST_PROGRAM).await?;
+
+        self.ll.otp_wdata().write(|w| w.value(value)).await?;
+        self.ll.otp_addr().write(|w| w.otp_addr(addr)).await?;
+        self.ll
+            .otp_cfg()
+            .write(|w| w.otp_man(1).otp_write(1))
+            .await?;
+
+        // Programming a word takes a few hundred microseconds
+        let mut done = false;
+        for _ in 0..OTP_PROG_TIMEOUT_US / OTP_PROG_POLL_US {
+            if self.ll.otp_stat().read().await?.otp_prog_done() == 1 {
+                done = true;
+                break;
+            }
+            delay_ns.delay_us(OTP_PROG_POLL_US).await;
+        }
+
+        // Go back to reading, leave manual mode and clear the status
+        self.write_otp_mode(OTP_MR_READ).await?;
+        self.ll.otp_cfg().write(|w| w).await?;
+        self.ll.otp_stat().write(|w| w.otp_prog_done(1)).await?;
+
+        if !done {
+            return Err(Error::OtpTimeout);
+        }
+
+        Ok(())
+    }
+
+    /// Writes the mode register of the OTP memory
+    #[maybe_async_attr]
+    async fn write_otp_mode(&mut self, mode: u32) -> Result<(), Error<SPI>> {
+        self.ll.otp_wdata().write(|w| w.value(mode)).await?;
+        self.ll
+            .otp_cfg()
+            .write(|w| w.otp_man(1).otp_write_mr(1))
+            .await?;
+        self.ll.otp_cfg().write(|w| w.otp_man(1)).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use embedded_hal_mock::eh1::spi::{Mock as SpiMock, Transaction as SpiTransaction};
+
+    use crate::test_utils::{read, write};
+
+    /// `embedded-hal-mock` has no asynchronous delay
+    struct NoDelay;
+
+    #[cfg(not(feature = "async"))]
+    impl DelayNs for NoDelay {
+        fn delay_ns(&mut self, _ns: u32) {}
+    }
+
+    #[cfg(feature = "async")]
+    impl DelayNs for NoDelay {
+        async fn delay_ns(&mut self, _ns: u32) {}
+    }
+
+    /// Adds up the time it was asked to wait
+    #[derive(Default)]
+    struct TotalDelay(u32);
+
+    #[cfg(not(feature = "async"))]
+    impl DelayNs for TotalDelay {
+        fn delay_ns(&mut self, ns: u32) {
+            self.0 += ns;
+        }
+    }
+
+    #[cfg(feature = "async")]
+    impl DelayNs for TotalDelay {
+        async fn delay_ns(&mut self, ns: u32) {
+            self.0 += ns;
+        }
+    }
+
+    /// The transactions of `read_otp`
+    fn read_otp(addr: u16, value: u32) -> Vec<Vec<SpiTransaction<u8>>> {
+        vec![
+            write(0x0B, 0x08, &[0x01, 0x00]),
+            read(0x0B, 0x04, &[0; 4]),
+            write(0x0B, 0x04, &(addr as u32).to_le_bytes()),
+            write(0x0B, 0x08, &[0x02, 0x00]),
+            read(0x0B, 0x10, &value.to_le_bytes()),
+        ]
+    }
+
+    #[test]
+    fn parse() {
+        let data = OtpData::from_words(&[
+            0x9ABC_DEF0,
+            0x1234_5678,
+            0x1001_0101,
+            0x0000_0011,
+            0x0000_0302,
+            0x0000_0041,
+            0x0000_1274,
+            0x0000_0085,
+            0x0013_0000,
+            0x4015_4010,
+            0x0000_012E,
+            0x0000_0002,
+            0,
+        ]);
+
+        assert_eq!(
+            data,
+            OtpData {
+                eui: Some(0x1234_5678_9ABC_DEF0),
+                ldo_tune: Some(0x0000_0011_1001_0101),
+                part_id: 0x302,
+                lot_id: 0x41,
+                vbat_ref: Some(0x74),
+                temp_ref: Some(0x85),
+                bias_tune: Some(0x13),
+                antenna_delay: Some((0x4010, 0x4015)),
+                xtal_trim: Some(0x2E),
+                revision: 2,
+                pll_lock_code: None,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_unprogrammed() {
+        let data = OtpData::from_words(&[0; 13]);
+
+        assert_eq!(data, OtpData::default());
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(all(feature = "async"), tokio::test))]
+    async fn apply_otp_antenna_delay() {
+        let expectations = [
+            read_otp(0x1C, 0x4015_4010),
+            vec![
+                read(0x0E, 0x00, &[0x00, 0x00, 0x10, 0x00]),
+                write(0x0E, 0x00, &[0x10, 0x40, 0x10, 0x00]),
+                write(0x01, 0x04, &[0x15, 0x40]),
+            ],
+            // Not programmed
+            read_otp(0x1C, 0),
+        ];
+        let spi = SpiMock::new(expectations.iter().flatten().flatten());
+        let mut dw3000 = DW3000::new(spi);
+
+        let delays = dw3000.apply_otp_antenna_delay().await.unwrap();
+        assert_eq!(delays, Some((0x4010, 0x4015)));
+
+        let delays = dw3000.apply_otp_antenna_delay().await.unwrap();
+        assert_eq!(delays, None);
+
+        dw3000.ll.spi.done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(all(feature = "async"), tokio::test))]
+    async fn program_otp_refuses_programmed_words() {
+        let expectations = [read_otp(0x1C, 0x4015_4010), read_otp(0x1C, 0x4015_4010)];
+        let spi = SpiMock::new(expectations.iter().flatten().flatten());
+        let mut dw3000 = DW3000::new(spi);
+
+        let result = dw3000.program_otp(0x1C, 0x4016_4011, false, NoDelay).await;
+        assert!(matches!(result, Err(Error::OtpAlreadyProgrammed)));
+
+        // Bits can't be cleared, even when overwriting
+        let result = dw3000.program_otp(0x1C, 0x4015_0000, true, NoDelay).await;
+        assert!(matches!(result, Err(Error::OtpAlreadyProgrammed)));
+
+        dw3000.ll.spi.done();
+    }
+
+    /// The transactions of `write_otp_mode`
+    fn write_otp_mode(mode: u32) -> Vec<Vec<SpiTransaction<u8>>> {
+        vec![
+            write(0x0B, 0x00, &mode.to_le_bytes()),
+            write(0x0B, 0x08, &[0x09, 0x00]),
+            write(0x0B, 0x08, &[0x01, 0x00]),
+        ]
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(all(feature = "async"), tokio::test))]
+    async fn program_otp_word() {
+        let ldo_tune = [0x14, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00];
+        let raised_ldo_tune = [0x14, 0x00, 0x00, 0x00, 0x00, 0xF0, 0x00, 0x00];
+        let expectations = [
+            read_otp(0x1C, 0),
+            vec![
+                // IDLE_RC
+                read(0x0F, 0x30, &[0x00, 0x00, 0x02, 0x00]),
+                read(0x07, 0x40, &ldo_tune),
+                write(0x07, 0x40, &raised_ldo_tune),
+                read(0x0B, 0x0C, &[0x02]),
+            ],
+            write_otp_mode(OTP_MR_PROGRAM),
+            write_otp_mode(OTP_MR_FAST_PROGRAM),
+            vec![
+                write(0x0B, 0x00, &0x4015_4010u32.to_le_bytes()),
+                write(0x0B, 0x04, &[0x1C, 0x00, 0x00, 0x00]),
+                write(0x0B, 0x08, &[0x05, 0x00]),
+                read(0x0B, 0x0C, &[0x02]),
+                read(0x0B, 0x0C, &[0x03]),
+            ],
+            write_otp_mode(OTP_MR_READ),
+            vec![
+                write(0x0B, 0x08, &[0x00, 0x00]),
+                write(0x0B, 0x0C, &[0x01]),
+                write(0x07, 0x40, &ldo_tune),
+            ],
+            read_otp(0x1C, 0x4015_4010),
+        ];
+        let spi = SpiMock::new(expectations.iter().flatten().flatten());
+        let mut dw3000 = DW3000::new(spi);
+
+        dw3000
+            .program_otp(0x1C, 0x4015_4010, false, NoDelay)
+            .await
+            .unwrap();
+
+        dw3000.ll.spi.done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(all(feature = "async"), tokio::test))]
+    async fn program_otp_requires_idle_rc() {
+        let expectations = [
+            read_otp(0x1C, 0),
+            // IDLE_PLL
+            vec![read(0x0F, 0x30, &[0x00, 0x00, 0x03, 0x00])],
+        ];
+        let spi = SpiMock::new(expectations.iter().flatten().flatten());
+        let mut dw3000 = DW3000::new(spi);
+
+        let result = dw3000.program_otp(0x1C, 0x4015_4010, false, NoDelay).await;
+        assert!(matches!(result, Err(Error::OtpNotIdleRc)));
+
+        dw3000.ll.spi.done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(all(feature = "async"), tokio::test))]
+    async fn program_otp_times_out() {
+        let ldo_tune = [0x14, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00];
+        let raised_ldo_tune = [0x14, 0x00, 0x00, 0x00, 0x00, 0xF0, 0x00, 0x00];
+        let polls = (OTP_PROG_TIMEOUT_US / OTP_PROG_POLL_US) as usize;
+        let expectations = [
+            read_otp(0x1C, 0),
+            vec![
+                // IDLE_RC
+                read(0x0F, 0x30, &[0x00, 0x00, 0x02, 0x00]),
+                read(0x07, 0x40, &ldo_tune),
+                write(0x07, 0x40, &raised_ldo_tune),
+                read(0x0B, 0x0C, &[0x02]),
+            ],
+            write_otp_mode(OTP_MR_PROGRAM),
+            write_otp_mode(OTP_MR_FAST_PROGRAM),
+            vec![
+                write(0x0B, 0x00, &0x4015_4010u32.to_le_bytes()),
+                write(0x0B, 0x04, &[0x1C, 0x00, 0x00, 0x00]),
+                write(0x0B, 0x08, &[0x05, 0x00]),
+            ],
+            vec![read(0x0B, 0x0C, &[0x02]); polls],
+            write_otp_mode(OTP_MR_READ),
+            vec![
+                write(0x0B, 0x08, &[0x00, 0x00]),
+                write(0x0B, 0x0C, &[0x01]),
+                write(0x07, 0x40, &ldo_tune),
+            ],
+        ];
+        let spi = SpiMock::new(expectations.iter().flatten().flatten());
+        let mut dw3000 = DW3000::new(spi);
+
+        let mut delay = TotalDelay::default();
+        let result = dw3000
+            .program_otp(0x1C, 0x4015_4010, false, &mut delay)
+            .await;
+        assert!(matches!(result, Err(Error::OtpTimeout)));
+        assert_eq!(delay.0, OTP_PROG_TIMEOUT_US * 1000);
+
+        dw3000.ll.spi.done();
+    }
+}
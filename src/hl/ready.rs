@@ -6,39 +6,94 @@ use byte::BytesExt as _;
 
 use super::AutoDoubleBufferReceiving;
 use crate::{
-    configs::{PdoaMode, SfdSequence, TxContinuation},
-    maybe_async_attr, spi_type,
-    time::Instant,
-    Config, Error, FastCommand, Ready, Sending, SingleBufferReceiving, Sleeping, DW3000,
+    configs::{PdoaMode, SfdSequence, TxContinuation, UwbChannel},
+    gpio::{Gpio, Led},
+    ll, maybe_async_attr, spi_type,
+    time::{Duration, Instant},
+    Config, Error, FastCommand, Ready, Sending, SingleBufferReceiving, Sleeping, TestMode, DW3000,
 };
 
 use smoltcp::wire::{Ieee802154Address, Ieee802154Frame, Ieee802154Pan, Ieee802154Repr};
 
 /// The behaviour of the sync pin
+///
+/// See [`DW3000::set_sync_behaviour`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum SyncBehaviour {
     /// The sync pin does nothing
     None,
-    /// The radio time will reset to 0 when the sync pin is high and the clock
-    /// gives a rising edge
+    /// The radio time will reset to 0 on the next rising edge of the sync pin
+    ///
+    /// This is a one-shot reset. Afterwards, all timestamps are relative to
+    /// the sync edge.
     TimeBaseReset,
     /// When receiving, instead of reading the internal timestamp, the time
     /// since the last sync is given back.
+    ///
+    /// The DW3000 can't count the time since the last sync next to its system
+    /// time, so this is not supported. Use
+    /// [`SyncBehaviour::ExternalSyncWithReset`] instead.
     ExternalSync,
     /// When receiving, instead of reading the internal timestamp, the time
     /// since the last sync is given back. Also resets the internal timebase
     /// back to 0.
+    ///
+    /// On the DW3000, this is the same as [`SyncBehaviour::TimeBaseReset`]:
+    /// after the reset, the system time is the time since the sync.
     ExternalSyncWithReset,
 }
 
+/// The outcome of [`DW3000::send_with_ack`]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DeliveryOutcome {
+    /// A matching acknowledgement was received
+    Acknowledged {
+        /// The number of transmissions it took, including the first one
+        attempts: u8,
+        /// The frame pending bit of the acknowledgement, set if the recipient
+        /// has data waiting for us
+        frame_pending: bool,
+    },
+    /// No matching acknowledgement was received after all attempts
+    NoAck,
+}
+
 /// The time at which the transmission will start
 pub enum SendTime {
     /// As fast as possible
     Now,
     /// After some time
     Delayed(Instant),
-    /// After the sync pin is engaged. (Only works when sync setup is in
-    /// ExternalSync mode)
-    OnSync,
+    /// After some time, counted from the RX timestamp of the last received
+    /// frame
+    ///
+    /// The lowest 9 bits of the duration are ignored.
+    AfterLastRx(Duration),
+    /// After some time, counted from the TX timestamp of the last sent frame
+    ///
+    /// The lowest 9 bits of the duration are ignored.
+    AfterLastTx(Duration),
+    /// After some time, counted from the reference time set with
+    /// [`DW3000::set_reference_time`]
+    ///
+    /// The lowest 9 bits of the duration are ignored.
+    AfterReference(Duration),
+    /// After some time, counted from the next rising edge of the sync pin
+    ///
+    /// Arms the one-shot timebase reset (see [`SyncBehaviour::TimeBaseReset`])
+    /// and schedules the frame at the given time after the reset. The sync
+    /// edge has to arrive before the system time wraps around, which takes
+    /// about 17 s. The lowest 9 bits of the duration are ignored.
+    OnSync(Duration),
+    /// As fast as possible, but only if no other transmission is going on
+    ///
+    /// The receiver first listens for a preamble for the given number of PAC
+    /// intervals (the chip adds one more PAC). If a preamble is detected, the
+    /// frame is not sent and [`DW3000::s_wait`] returns
+    /// `Error::ChannelBusy`.
+    WhenChannelClear(u16),
 }
 
 /// The time at which the reception will start
@@ -47,6 +102,20 @@ pub enum ReceiveTime {
     Now,
     /// After some time
     Delayed(Instant),
+    /// After some time, counted from the TX timestamp of the last sent frame
+    ///
+    /// The lowest 9 bits of the duration are ignored.
+    AfterLastTx(Duration),
+    /// After some time, counted from the RX timestamp of the last received
+    /// frame
+    ///
+    /// The lowest 9 bits of the duration are ignored.
+    AfterLastRx(Duration),
+    /// After some time, counted from the reference time set with
+    /// [`DW3000::set_reference_time`]
+    ///
+    /// The lowest 9 bits of the duration are ignored.
+    AfterReference(Duration),
 }
 
 impl<SPI> DW3000<SPI, Ready>
@@ -66,6 +135,21 @@ where
         Ok(())
     }
 
+    /// Sets the reference time for delayed operations
+    ///
+    /// Used by [`SendTime::AfterReference`] and [`ReceiveTime::AfterReference`]
+    /// to schedule a transmission or reception relative to `time`. The lowest
+    /// 9 bits of `time` are ignored.
+    #[maybe_async_attr]
+    pub async fn set_reference_time(&mut self, time: Instant) -> Result<(), Error<SPI>> {
+        self.ll
+            .dref_time()
+            .write(|w| w.value(time.dx_time()))
+            .await?;
+
+        Ok(())
+    }
+
     /// Sets the network id and address used for sending and receiving
     #[maybe_async_attr]
     pub async fn set_address(
@@ -195,6 +279,16 @@ where
     ) -> Result<usize, Error<SPI>> {
         let frame_header = self.build_frame_header(dst_addr, dst_pan_id).await?;
 
+        Ok(Self::emit_frame(buffer, &frame_header, data))
+    }
+
+    /// Writes `frame_header` and `data` to `buffer` and returns the length of
+    /// the message (Header + Data)
+    pub(crate) fn emit_frame(
+        buffer: &mut [u8],
+        frame_header: &Ieee802154Repr,
+        data: &[u8],
+    ) -> usize {
         let mut frame = Ieee802154Frame::new_unchecked(&mut buffer[0..]);
         frame_header.emit(&mut frame);
 
@@ -206,7 +300,25 @@ where
         // footer
         buffer[len] = 0x00;
 
-        Ok(len)
+        len
+    }
+
+    /// Checks that the frame that [`DW3000::emit_frame`] builds from
+    /// `frame_header` and `data` can be sent
+    ///
+    /// The frame is sent with two more bytes after it, starting with the
+    /// footer, and the DW3000 appends the two-byte FCS. Header and data can
+    /// take up at most 123 of the 127 bytes, or [`Error::FrameTooLong`] is
+    /// returned.
+    pub(crate) fn check_frame_len(
+        frame_header: &Ieee802154Repr,
+        data: &[u8],
+    ) -> Result<(), Error<SPI>> {
+        if frame_header.buffer_len() + data.len() + 4 > 127 {
+            return Err(Error::FrameTooLong);
+        }
+
+        Ok(())
     }
 
     /// Send an raw UWB PHY frame
@@ -227,7 +339,9 @@ where
     /// is in the `Sending` state, and can be used to wait for the transmission
     /// to finish and check its result.
     ///
-    /// Will panic if the delayed TX time is not rounded to top 31 bits.
+    /// Returns [`Error::FrameTooLong`] if `data` and the FCS that the DW3000
+    /// appends don't fit into 127 bytes. The lowest 9 bits of a delayed TX
+    /// time are ignored.
     #[maybe_async_attr]
     pub async fn send_raw(
         mut self,
@@ -241,19 +355,7 @@ where
         // self.enable_tx_clock().await;
 
         // Prepare transmitter
-        let mut len: usize = 0;
-        self.ll
-            .tx_buffer()
-            .write(|w| {
-                let result = w.data().write(&mut len, data);
-
-                if let Err(err) = result {
-                    panic!("Failed to write frame: {:?}", err);
-                }
-
-                w
-            })
-            .await?;
+        let len = self.write_tx_buffer(data).await?;
 
         let txb_offset = 0; // no offset in TX_BUFFER
         let mut txb_offset_errata = txb_offset;
@@ -275,42 +377,95 @@ where
             })
             .await?;
 
+        let rx_after = matches!(continuation, TxContinuation::Rx);
+        if rx_after {
+            self.set_rx_timeouts(&config).await?;
+            self.set_receive_mode(&config).await?;
+        }
+
         match send_time {
             SendTime::Delayed(time) => {
-                // Panic if the time is not rounded to top 31 bits
-                //
-                // NOTE: DW3000's DX_TIME register is 32 bits wide, but only the top 31 bits are used.
-                // The last bit is ignored per the user manual!!!
-                if time.value() % (1 << 9) != 0 {
-                    panic!("Time must be rounded to top 31 bits!");
-                }
-
                 // Put the time into the delay register
                 // By setting this register, the chip knows to delay before transmitting
+                self.ll.dx_time().write(|w| w.value(time.dx_time())).await?;
+                if rx_after {
+                    self.fast_cmd(FastCommand::CMD_DTX_W4R).await?;
+                } else {
+                    self.fast_cmd(FastCommand::CMD_DTX).await?;
+                }
+            }
+            SendTime::AfterLastRx(delay) => {
                 self.ll
                     .dx_time()
-                    .modify(|_, w| // 32-bits value of the most significant bits
-                    w.value( (time.value() >> 8) as u32 ))
+                    .write(|w| w.value(delay.dx_time()))
                     .await?;
-                if matches!(continuation, TxContinuation::Ready) {
-                    self.fast_cmd(FastCommand::CMD_DTX).await?;
+                if rx_after {
+                    self.fast_cmd(FastCommand::CMD_DTX_RS_W4R).await?;
                 } else {
-                    self.fast_cmd(FastCommand::CMD_DTX_W4R).await?;
+                    self.fast_cmd(FastCommand::CMD_DTX_RS).await?;
                 }
             }
-            SendTime::OnSync => {
-                self.ll.ec_ctrl().modify(|_, w| w.ostr_mode(1)).await?;
-                self.ll.ec_ctrl().modify(|_, w| w.osts_wait(33)).await?;
+            SendTime::AfterLastTx(delay) => {
+                self.ll
+                    .dx_time()
+                    .write(|w| w.value(delay.dx_time()))
+                    .await?;
+                if rx_after {
+                    self.fast_cmd(FastCommand::CMD_DTX_TS_W4R).await?;
+                } else {
+                    self.fast_cmd(FastCommand::CMD_DTX_TS).await?;
+                }
+            }
+            SendTime::AfterReference(delay) => {
+                self.ll
+                    .dx_time()
+                    .write(|w| w.value(delay.dx_time()))
+                    .await?;
+                if rx_after {
+                    self.fast_cmd(FastCommand::CMD_DTX_REF_W4R).await?;
+                } else {
+                    self.fast_cmd(FastCommand::CMD_DTX_REF).await?;
+                }
+            }
+            SendTime::OnSync(delay) => {
+                self.set_sync_behaviour(SyncBehaviour::TimeBaseReset)
+                    .await?;
+                // After the reset, the system time counts from the sync edge
+                self.ll
+                    .dx_time()
+                    .write(|w| w.value(delay.dx_time()))
+                    .await?;
+                if rx_after {
+                    self.fast_cmd(FastCommand::CMD_DTX_W4R).await?;
+                } else {
+                    self.fast_cmd(FastCommand::CMD_DTX).await?;
+                }
             }
             SendTime::Now => {
-                if matches!(continuation, TxContinuation::Ready) {
+                if rx_after {
+                    self.fast_cmd(FastCommand::CMD_TX_W4R).await?
+                } else {
                     self.fast_cmd(FastCommand::CMD_TX).await?
+                }
+            }
+            SendTime::WhenChannelClear(timeout) => {
+                // A zero timeout disables the preamble detection timeout, and
+                // the CCA would never finish
+                if timeout == 0 {
+                    return Err(Error::InvalidConfiguration);
+                }
+
+                self.ll.pre_toc().write(|w| w.value(timeout)).await?;
+                if rx_after {
+                    self.fast_cmd(FastCommand::CMD_CCA_TX_W4R).await?
                 } else {
-                    self.fast_cmd(FastCommand::CMD_TX_W4R).await?
+                    self.fast_cmd(FastCommand::CMD_CCA_TX).await?
                 }
             }
         }
 
+        let cca = matches!(send_time, SendTime::WhenChannelClear(_));
+
         Ok(DW3000 {
             ll: self.ll,
             seq: self.seq,
@@ -318,10 +473,35 @@ where
                 finished: false,
                 continuation,
                 config,
+                cca,
             },
         })
     }
 
+    /// Writes `data` to the TX buffer and returns the number of bytes written
+    ///
+    /// Returns [`Error::FrameTooLong`] if `data` and the FCS that the DW3000
+    /// appends don't fit into 127 bytes.
+    #[maybe_async_attr]
+    async fn write_tx_buffer(&mut self, data: &[u8]) -> Result<usize, Error<SPI>> {
+        if data.len() + 2 > 127 {
+            return Err(Error::FrameTooLong);
+        }
+
+        let mut len: usize = 0;
+        let mut result = Ok(());
+        self.ll
+            .tx_buffer()
+            .write(|w| {
+                result = w.data().write(&mut len, data);
+                w
+            })
+            .await?;
+        result.map_err(|_| Error::FrameTooLong)?;
+
+        Ok(len)
+    }
+
     /// Send an IEEE 802.15.4 MAC frame
     ///
     /// The `frame` argument is an IEEE 802.15.4 MAC frame and sent to `destination`.
@@ -433,6 +613,87 @@ where
             .await;
     }
 
+    /// Send an IEEE 802.15.4 MAC frame and wait for its acknowledgement
+    ///
+    /// The `data` argument is wrapped into an IEEE 802.15.4 MAC frame with the
+    /// ack request bit set and sent to `address`. After each transmission the
+    /// receiver is enabled for `ack_timeout`, and the frame is sent again with
+    /// the same sequence number until an acknowledgement with a matching
+    /// sequence number arrives, up to `max_retries` times.
+    ///
+    /// Frames other than the acknowledgement that arrive in the meantime are
+    /// dropped, and the receiver keeps listening for the rest of
+    /// `ack_timeout`. Returns [`Error::FrameTooLong`] if the frame doesn't fit
+    /// into 127 bytes, which leaves 123 bytes for the header and `data` (see
+    /// `check_frame_len`).
+    ///
+    /// Unlike [`DW3000::send_to`], this method blocks until the exchange is
+    /// over and returns to the `Ready` state together with the outcome.
+    #[maybe_async_attr]
+    pub async fn send_with_ack(
+        mut self,
+        data: &[u8],
+        pan_id: Ieee802154Pan,
+        address: Ieee802154Address,
+        ack_timeout: Duration,
+        max_retries: u8,
+        config: Config,
+    ) -> Result<(DW3000<SPI, Ready>, DeliveryOutcome), Error<SPI>> {
+        let mut frame_header = self.build_frame_header(Some(address), Some(pan_id)).await?;
+        frame_header.ack_request = true;
+        let seq = frame_header.sequence_number.unwrap_or_default();
+
+        Self::check_frame_len(&frame_header, data)?;
+        let mut buffer = [0_u8; 127];
+        let len = Self::emit_frame(&mut buffer, &frame_header, data);
+
+        let ack_config = Config {
+            rx_frame_wait_timeout: Some(ack_timeout),
+            ..config
+        };
+
+        let mut radio = self;
+        for attempt in 1..=max_retries.saturating_add(1) {
+            let mut sending = radio
+                .send_raw(
+                    &buffer[0..len + 2],
+                    SendTime::Now,
+                    TxContinuation::Rx,
+                    ack_config,
+                )
+                .await?;
+            let sent = loop {
+                match sending.s_wait().await {
+                    Ok(sent) => break sent,
+                    Err(nb::Error::WouldBlock) => continue,
+                    Err(nb::Error::Other(error)) => return Err(error),
+                }
+            };
+
+            let mut receiving = sending
+                .continue_receiving()
+                .await
+                .map_err(|(_, error)| error)?;
+            let ack = receiving.wait_for_ack(seq, sent).await?;
+            radio = receiving
+                .finish_receiving()
+                .await
+                .map_err(|(_, error)| error)?;
+
+            if let Some(frame_pending) = ack {
+                return Ok((
+                    radio,
+                    DeliveryOutcome::Acknowledged {
+                        attempts: attempt,
+                        frame_pending,
+                    },
+                ));
+            }
+        }
+
+        Ok((radio, DeliveryOutcome::NoAck))
+    }
+
     /// Attempt to receive a single IEEE 802.15.4 MAC frame
     ///
     /// Initializes the receiver. The method consumes this instance of `DW3000`
@@ -487,6 +748,148 @@ where
         Ok(rx_radio)
     }
 
+    /// Start transmitting a continuous wave for regulatory testing
+    ///
+    /// The transmitter outputs an unmodulated carrier at the center frequency
+    /// of `channel` until [`DW3000::finish_test_mode`] is called.
+    ///
+    /// The PLL must already be configured for `channel` by [`DW3000::config`],
+    /// otherwise `Error::InvalidConfiguration` is returned.
+    #[maybe_async_attr]
+    pub async fn start_continuous_wave(
+        mut self,
+        channel: UwbChannel,
+    ) -> Result<DW3000<SPI, TestMode>, Error<SPI>> {
+        if self.ll.chan_ctrl().read().await?.rf_chan() != channel as u8 {
+            return Err(Error::InvalidConfiguration);
+        }
+
+        self.force_idle().await?;
+        let state = self.enable_rf_tx(channel).await?;
+
+        // Enable all the transmitter test bits, which selects the CW mode
+        self.ll.tx_test().write(|w| w.tx_entest(0xF)).await?;
+
+        Ok(DW3000 {
+            ll: self.ll,
+            seq: self.seq,
+            state,
+        })
+    }
+
+    /// Start transmitting the same frame repeatedly for regulatory testing
+    ///
+    /// The `frame` is sent over and over with the channel, bitrate and
+    /// preamble length set by [`DW3000::config`], until
+    /// [`DW3000::finish_test_mode`] is called. A new transmission starts every
+    /// `repetition_period`, which has a resolution of 512 DW3000 time units
+    /// (about 8 ns). The period must be longer than the frame itself.
+    ///
+    /// Returns [`Error::FrameTooLong`] if `frame` and its FCS don't fit into
+    /// 127 bytes.
+    #[maybe_async_attr]
+    pub async fn start_continuous_frame(
+        mut self,
+        frame: &[u8],
+        repetition_period: Duration,
+    ) -> Result<DW3000<SPI, TestMode>, Error<SPI>> {
+        // Checked before the transmitter is turned on
+        if frame.len() + 2 > 127 {
+            return Err(Error::FrameTooLong);
+        }
+
+        let channel = match self.ll.chan_ctrl().read().await?.rf_chan() {
+            0 => UwbChannel::Channel5,
+            1 => UwbChannel::Channel9,
+            _ => return Err(Error::InvalidConfiguration),
+        };
+
+        self.force_idle().await?;
+        let state = self.enable_rf_tx(channel).await?;
+
+        let len = self.write_tx_buffer(frame).await?;
+        self.ll
+            .tx_fctrl()
+            .modify(|_, w| {
+                w.txflen(len as u16 + 2) // data length + two-octet CRC
+                    .txb_offset(0) // no offset in TX_BUFFER
+            })
+            .await?;
+
+        // In this mode DX_TIME holds the repetition period in units of 512
+        // DW3000 time units. The minimum value allowed by the chip is 2.
+        // `Duration` is at most 40 bits wide, so the cast never truncates.
+        let period = ((repetition_period.value() >> 9) as u32).max(2);
+        self.ll.dx_time().write(|w| w.value(period)).await?;
+
+        // Transmit Power Spectrum Test Mode
+        self.ll.diag_tmc().modify(|_, w| w.tx_pstm(0b1)).await?;
+        self.fast_cmd(FastCommand::CMD_TX).await?;
+
+        Ok(DW3000 {
+            ll: self.ll,
+            seq: self.seq,
+            state,
+        })
+    }
+
+    /// Manually enable the transmitter RF blocks, LDOs and clocks
+    ///
+    /// Normally the chip sequences these itself during a transmission, but the
+    /// test modes need them to stay on. Returns the test mode state, which
+    /// holds the previous register values so they can be restored.
+    #[maybe_async_attr]
+    async fn enable_rf_tx(&mut self, channel: UwbChannel) -> Result<TestMode, Error<SPI>> {
+        // TX_SW_EN | TX_EN_BUF | TX_EN | TX_BIAS_EN, plus TX_CH5 on channel 5
+        let rf_tx = match channel {
+            UwbChannel::Channel5 => 0x0200_3C00,
+            UwbChannel::Channel9 => 0x0200_1C00,
+        };
+        // The enable (lower half) and VREF (upper half) bits of the VDDTX1,
+        // VDDTX2 and VDDHVTX LDOs
+        const LDO_TX: u16 = 0x0860;
+
+        let rf_enable = self.ll.rf_enable().read().await?.value();
+        self.ll
+            .rf_enable()
+            .write(|w| w.value(rf_enable | rf_tx))
+            .await?;
+        let rf_ctrl_mask = self.ll.rf_ctrl_mask().read().await?.value();
+        self.ll
+            .rf_ctrl_mask()
+            .write(|w| w.value(rf_ctrl_mask | rf_tx))
+            .await?;
+
+        let ldo_ctrl = self.ll.ldo_ctrl().read().await?;
+        let (ldo_low, ldo_high) = (ldo_ctrl.low(), ldo_ctrl.high());
+        self.ll
+            .ldo_ctrl()
+            .write(|w| w.low(ldo_low | LDO_TX).high(ldo_high | LDO_TX))
+            .await?;
+
+        // Force the system and transmitter clocks to the PLL
+        let mut clk_ctrl = self.ll.clk_ctrl().read().await?;
+        let mut saved_clk_ctrl = [0; 4];
+        let buffer = <ll::CLK_CTRL as ll::Readable>::buffer(&mut clk_ctrl);
+        saved_clk_ctrl.copy_from_slice(&buffer[buffer.len() - 4..]);
+        self.ll
+            .clk_ctrl()
+            .write(|w| {
+                let buffer = <ll::CLK_CTRL as ll::Writable>::buffer(w);
+                let len = buffer.len();
+                buffer[len - 4..].copy_from_slice(&saved_clk_ctrl);
+                w.sys_clk(0b10).tx_clk(0b10)
+            })
+            .await?;
+
+        Ok(TestMode {
+            rf_enable,
+            rf_ctrl_mask,
+            ldo_ctrl: (ldo_high as u32) << 16 | ldo_low as u32,
+            clk_ctrl: saved_clk_ctrl,
+        })
+    }
+
     /// Disable the SPIRDY interrupt flag
     #[maybe_async_attr]
     pub async fn disable_spirdy_interrupt(&mut self) -> Result<(), Error<SPI>> {
@@ -499,7 +902,10 @@ where
     /// Overwrites any interrupt flags that were previously set.
     #[maybe_async_attr]
     pub async fn enable_tx_interrupts(&mut self) -> Result<(), Error<SPI>> {
-        self.ll.sys_enable().modify(|_, w| w.txfrs_en(0b1)).await?;
+        self.ll
+            .sys_enable()
+            .modify(|_, w| w.txfrs_en(0b1).cca_fail_en(0b1))
+            .await?;
         Ok(())
     }
 
@@ -811,6 +1217,198 @@ where
         Ok(output)
     }
 
+    /// Configures what the sync pin (GPIO7) does
+    ///
+    /// For [`SyncBehaviour::TimeBaseReset`] and
+    /// [`SyncBehaviour::ExternalSyncWithReset`], GPIO7 is switched to its
+    /// SYNC function and the one-shot timebase reset is armed. The system
+    /// time resets to 0 on the next rising edge, and the reset has to be armed
+    /// again for the next one. [`SyncBehaviour::None`] disarms it.
+    ///
+    /// [`SyncBehaviour::ExternalSync`] is not supported by the DW3000 and
+    /// returns `Error::InvalidConfiguration`.
+    #[maybe_async_attr]
+    pub async fn set_sync_behaviour(&mut self, behaviour: SyncBehaviour) -> Result<(), Error<SPI>> {
+        match behaviour {
+            SyncBehaviour::None => {
+                self.ll.ec_ctrl().modify(|_, w| w.ostr_mode(0)).await?;
+            }
+            SyncBehaviour::TimeBaseReset | SyncBehaviour::ExternalSyncWithReset => {
+                self.ll.gpio_mode().modify(|_, w| w.msgp7(0)).await?;
+                // Number of clock cycles between the edge and the reset
+                self.ll
+                    .ec_ctrl()
+                    .modify(|_, w| w.osts_wait(33).ostr_mode(1))
+                    .await?;
+            }
+            SyncBehaviour::ExternalSync => return Err(Error::InvalidConfiguration),
+        }
+
+        Ok(())
+    }
+
+    /// Switches GPIO0 to GPIO3 to their LED functions
+    ///
+    /// Each LED in `leds` blinks for `blink_ms` milliseconds on its event. The
+    /// duration is rounded to units of 14 ms, and must be between 14 ms and
+    /// 3570 ms. LEDs not in `leds` are left as they are.
+    #[maybe_async_attr]
+    pub async fn enable_leds(&mut self, leds: Led, blink_ms: u32) -> Result<(), Error<SPI>> {
+        let blink_tim = Led::blink_time(blink_ms).ok_or(Error::InvalidConfiguration)?;
+
+        // The blink timer runs on the kilohertz clock
+        self.ll
+            .clk_ctrl()
+            .modify(|_, w| {
+                w.gpio_clk_en(0b1)
+                    .gpio_dclk_en(0b1)
+                    .gpio_drst_n(0b1)
+                    .lp_clk_en(0b1)
+            })
+            .await?;
+
+        self.ll
+            .led_ctrl()
+            .modify(|_, w| w.blink_en(0b1).blink_tim(blink_tim))
+            .await?;
+
+        self.set_led_modes(leds, 1).await
+    }
+
+    /// Switches the LEDs in `leds` back to GPIO
+    #[maybe_async_attr]
+    pub async fn disable_leds(&mut self, leds: Led) -> Result<(), Error<SPI>> {
+        self.set_led_modes(leds, 0).await
+    }
+
+    /// Blinks the LEDs in `leds` once, regardless of their events
+    ///
+    /// The LEDs must have been enabled with [`DW3000::enable_leds`]. Use
+    /// `Led::all()` to flash every LED, for example on boot.
+    #[maybe_async_attr]
+    pub async fn blink_leds(&mut self, leds: Led) -> Result<(), Error<SPI>> {
+        // The trigger only takes effect on a transition from 0 to 1
+        self.ll
+            .led_ctrl()
+            .modify(|_, w| w.force_trig(leds.bits()))
+            .await?;
+        self.ll.led_ctrl().modify(|_, w| w.force_trig(0)).await?;
+
+        Ok(())
+    }
+
+    #[maybe_async_attr]
+    async fn set_led_modes(&mut self, leds: Led, mode: u8) -> Result<(), Error<SPI>> {
+        self.ll
+            .gpio_mode()
+            .modify(|r, w| {
+                let mode_of = |led, current| if leds.contains(led) { mode } else { current };
+                w.msgp0(mode_of(Led::RX_OK, r.msgp0()))
+                    .msgp1(mode_of(Led::SFD, r.msgp1()))
+                    .msgp2(mode_of(Led::RX, r.msgp2()))
+                    .msgp3(mode_of(Led::TX, r.msgp3()))
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Switches the pin of `gpio` from its alternate function to GPIO
+    ///
+    /// Also enables the GPIO clocks, including the kilohertz clock that
+    /// debounces GPIO interrupts. These registers are shared with other
+    /// functions of the radio, so the [`Gpio`] handles leave them alone.
+    #[maybe_async_attr]
+    pub async fn select_gpio_mode<S, const N: u8, MODE>(
+        &mut self,
+        _gpio: &Gpio<S, N, MODE>,
+    ) -> Result<(), Error<SPI>> {
+        self.ll
+            .clk_ctrl()
+            .modify(|_, w| {
+                w.gpio_clk_en(0b1)
+                    .gpio_dclk_en(0b1)
+                    .gpio_drst_n(0b1)
+                    .lp_clk_en(0b1)
+            })
+            .await?;
+
+        // GPIO7 and GPIO8 default to SYNC and IRQ, the other pins default to
+        // GPIO
+        self.ll
+            .gpio_mode()
+            .modify(|_, w| match N {
+                0 => w.msgp0(0),
+                1 => w.msgp1(0),
+                2 => w.msgp2(0),
+                3 => w.msgp3(0),
+                4 => w.msgp4(0),
+                5 => w.msgp5(0),
+                6 => w.msgp6(0),
+                7 => w.msgp7(1),
+                _ => w.msgp8(1),
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Enables the `GPIO` event in the interrupt mask, so GPIO interrupts
+    /// drive the IRQ pin
+    ///
+    /// See [`Gpio::enable_interrupt`].
+    #[maybe_async_attr]
+    pub async fn enable_gpio_interrupts(&mut self) -> Result<(), Error<SPI>> {
+        self.ll
+            .sys_enable()
+            .modify(|_, w| w.gpioirq_en(0b1))
+            .await?;
+        Ok(())
+    }
+
+    /// Returns the pins whose interrupt has triggered
+    ///
+    /// See [`Gpio::enable_interrupt`].
+    #[maybe_async_attr]
+    pub async fn get_gpio_interrupt_status(&mut self) -> Result<[u8; 9], Error<SPI>> {
+        let gpio_ists = self.ll.gpio_ists().read().await?;
+        let status = [
+            gpio_ists.gists0(),
+            gpio_ists.gists1(),
+            gpio_ists.gists2(),
+            gpio_ists.gists3(),
+            gpio_ists.gists4(),
+            gpio_ists.gists5(),
+            gpio_ists.gists6(),
+            gpio_ists.gists7(),
+            gpio_ists.gists8(),
+        ];
+
+        Ok(status)
+    }
+
+    /// Clears the interrupt latches of all pins and the `GPIO` event
+    #[maybe_async_attr]
+    pub async fn clear_gpio_interrupts(&mut self) -> Result<(), Error<SPI>> {
+        self.ll
+            .gpio_iclr()
+            .write(|w| {
+                w.giclr0(1)
+                    .giclr1(1)
+                    .giclr2(1)
+                    .giclr3(1)
+                    .giclr4(1)
+                    .giclr5(1)
+                    .giclr6(1)
+                    .giclr7(1)
+                    .giclr8(1)
+            })
+            .await?;
+        self.ll.sys_status().write(|w| w.gpioirq(0b1)).await?;
+
+        Ok(())
+    }
+
     /// Returns current raw state / input
     #[maybe_async_attr]
     pub async fn get_gpio_raw_state(&mut self) -> Result<[u8; 9], Error<SPI>> {
@@ -900,3 +1498,61 @@ impl ConfigGPIOs {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use embedded_hal_mock::eh1::spi::Mock as SpiMock;
+
+    use crate::test_utils::read;
+
+    /// PAN 0x0605, short address 0x0403
+    const PANADR: [u8; 4] = [0x03, 0x04, 0x05, 0x06];
+
+    /// The destination of the frames, which makes for a 9-byte header
+    const DST: Ieee802154Address = Ieee802154Address::Short([0x02, 0x01]);
+
+    #[maybe_async::test(not(feature = "async"), async(all(feature = "async"), tokio::test))]
+    async fn test_frame_len() {
+        let spi = SpiMock::new(&read(0x00, 0x0C, &PANADR));
+        let mut dw3000 = DW3000::new_ready(spi);
+
+        let frame_header = dw3000
+            .build_frame_header(Some(DST), Some(Ieee802154Pan(0x0605)))
+            .await
+            .unwrap();
+        assert_eq!(frame_header.buffer_len(), 9);
+
+        // 123 bytes of header and data, 127 bytes with the two trailing bytes
+        // and the FCS
+        let result = DW3000::<SpiMock<u8>, Ready>::check_frame_len(&frame_header, &[0; 114]);
+        assert!(result.is_ok());
+        let result = DW3000::<SpiMock<u8>, Ready>::check_frame_len(&frame_header, &[0; 115]);
+        assert!(matches!(result, Err(Error::FrameTooLong)));
+
+        dw3000.ll.spi.done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(all(feature = "async"), tokio::test))]
+    async fn test_send_with_ack_frame_too_long() {
+        let spi = SpiMock::new(&read(0x00, 0x0C, &PANADR));
+        let mut spi_done = spi.clone();
+        let dw3000 = DW3000::new_ready(spi);
+
+        // 124 bytes of header and data
+        let result = dw3000
+            .send_with_ack(
+                &[0; 115],
+                Ieee802154Pan(0x0605),
+                DST,
+                Duration::from_nanos(1_000_000),
+                0,
+                Config::default(),
+            )
+            .await;
+        assert!(matches!(result, Err(Error::FrameTooLong)));
+
+        spi_done.done();
+    }
+}
@@ -14,11 +14,11 @@ use super::{AutoDoubleBufferReceiving, ReceiveTime, Receiving};
 use crate::{
     configs::{AutoAck, BitRate, PulseRepetitionFrequency, SfdSequence},
     maybe_async_attr, spi_type,
-    time::Instant,
-    Config, Error, FastCommand, Ready, DW3000,
+    time::{Duration, Instant},
+    Config, Error, FastCommand, Ready, SingleBufferReceiving, DW3000,
 };
 
-use smoltcp::wire::Ieee802154Frame;
+use smoltcp::wire::{Ieee802154Frame, Ieee802154FrameType};
 
 /// An incoming message
 #[derive(Debug)]
@@ -76,21 +76,29 @@ where
         recv_time: ReceiveTime,
         config: Config,
     ) -> Result<(), Error<SPI>> {
-        if config.frame_filtering {
+        if let Some(filter) = config.frame_filter {
+            // The data pending bits live in the same register, so only touch
+            // the frame type bits
             self.ll
                 .ff_cfg()
                 .modify(|_, w| {
-                    w.ffab(0b1) // receive beacon frames
-                        .ffad(0b1) // receive data frames
-                        .ffaa(0b1) // receive acknowledgement frames
-                        .ffam(0b1) // receive MAC command frames
+                    w.ffab(filter.allow_beacon as u8)
+                        .ffad(filter.allow_data as u8)
+                        .ffaa(filter.allow_ack as u8)
+                        .ffam(filter.allow_mac_command as u8)
+                        .ffar(filter.allow_reserved as u8)
+                        .ffamulti(filter.allow_multipurpose as u8)
+                        .ffaf(filter.allow_fragmented as u8)
+                        .ffae(filter.allow_extended as u8)
+                        .ffbc(filter.behave_as_coordinator as u8)
+                        .ffib(filter.allow_mac_implicit_broadcast as u8)
                 })
                 .await?;
             self.ll
                 .sys_cfg()
                 .modify(|_, w| {
                     w.ffen(0b1). // enable frame filtering
-                    rxautr(0b1) // enable Receiver Auto-Re-enable for failed/filtered frames
+                    rxautr(filter.auto_reenable as u8) // Receiver Auto-Re-enable for failed/filtered frames
                 })
                 .await?;
         } else {
@@ -105,24 +113,36 @@ where
                 .await?;
         }
 
+        self.set_rx_timeouts(&config).await?;
+        self.set_receive_mode(&config).await?;
+
         match recv_time {
             ReceiveTime::Delayed(time) => {
-                // Panic if the time is not rounded to top 31 bits
-                //
-                // NOTE: DW3000's DX_TIME register is 32 bits wide, but only the top 31 bits are used.
-                // The last bit is ignored per the user manual!!!
-                if time.value() % (1 << 9) != 0 {
-                    panic!("Time must be rounded to top 31 bits!");
-                }
-
                 // Put the time into the delay register
-                // By setting this register, the chip knows to delay before transmitting
+                // By setting this register, the chip knows to delay before receiving
+                self.ll.dx_time().write(|w| w.value(time.dx_time())).await?;
+                self.fast_cmd(FastCommand::CMD_DRX).await?;
+            }
+            ReceiveTime::AfterLastTx(delay) => {
                 self.ll
                     .dx_time()
-                    .modify(|_, w| // 32-bits value of the most significant bits
-                    w.value( (time.value() >> 8) as u32 ))
+                    .write(|w| w.value(delay.dx_time()))
                     .await?;
-                self.fast_cmd(FastCommand::CMD_DRX).await?;
+                self.fast_cmd(FastCommand::CMD_DRX_TS).await?;
+            }
+            ReceiveTime::AfterLastRx(delay) => {
+                self.ll
+                    .dx_time()
+                    .write(|w| w.value(delay.dx_time()))
+                    .await?;
+                self.fast_cmd(FastCommand::CMD_DRX_RS).await?;
+            }
+            ReceiveTime::AfterReference(delay) => {
+                self.ll
+                    .dx_time()
+                    .write(|w| w.value(delay.dx_time()))
+                    .await?;
+                self.fast_cmd(FastCommand::CMD_DRX_REF).await?;
             }
             ReceiveTime::Now => self.fast_cmd(FastCommand::CMD_RX).await?,
         }
@@ -176,12 +196,18 @@ where
                 return Err(nb::Error::Other(Error::FrameFilteringRejection));
             }
             if sys_status.rxfto() == 0b1 {
+                // The receiver has turned itself off
+                self.clear_status().await.map_err(nb::Error::Other)?;
+                self.state.mark_finished();
                 return Err(nb::Error::Other(Error::FrameWaitTimeout));
             }
             if sys_status.rxovrr() == 0b1 {
                 return Err(nb::Error::Other(Error::Overrun));
             }
             if sys_status.rxpto() == 0b1 {
+                // The receiver has turned itself off
+                self.clear_status().await.map_err(nb::Error::Other)?;
+                self.state.mark_finished();
                 return Err(nb::Error::Other(Error::PreambleDetectionTimeout));
             }
 
@@ -307,12 +333,18 @@ where
                 return Err(nb::Error::Other(Error::FrameFilteringRejection));
             }
             if sys_status.rxfto() == 0b1 {
+                // The receiver has turned itself off
+                self.clear_status().await.map_err(nb::Error::Other)?;
+                self.state.mark_finished();
                 return Err(nb::Error::Other(Error::FrameWaitTimeout));
             }
             if sys_status.rxovrr() == 0b1 {
                 return Err(nb::Error::Other(Error::Overrun));
             }
             if sys_status.rxpto() == 0b1 {
+                // The receiver has turned itself off
+                self.clear_status().await.map_err(nb::Error::Other)?;
+                self.state.mark_finished();
                 return Err(nb::Error::Other(Error::PreambleDetectionTimeout));
             }
 
@@ -380,6 +412,45 @@ where
         Ok((len, rx_time, rx_quality))
     }
 
+    /// Wait for receive operation to finish, sleeping on the IRQ pin
+    ///
+    /// Unlike [`DW3000::r_wait`], this doesn't poll `SYS_STATUS` over SPI.
+    /// It is only read once before waiting and again whenever `irq` goes
+    /// high. Call [`DW3000::enable_rx_interrupts`] before receiving, and keep
+    /// other interrupts disabled, so the pin only rises when the reception is
+    /// over.
+    #[cfg(feature = "async")]
+    pub async fn wait_received<'b, IRQ>(
+        &mut self,
+        irq: &mut IRQ,
+        buffer: &'b mut [u8],
+    ) -> Result<Message<'b>, Error<SPI>>
+    where
+        IRQ: embedded_hal_async::digital::Wait,
+    {
+        let (len, rx_time, rx_quality) = loop {
+            match self.r_wait_buf(buffer).await {
+                Ok(result) => break result,
+                Err(nb::Error::Other(error)) => return Err(error),
+                Err(nb::Error::WouldBlock) => {}
+            }
+
+            irq.wait_for_high().await.map_err(|_| Error::Irq)?;
+        };
+
+        let frame = Ieee802154Frame::new_checked(&buffer[..len]).map_err(|_| {
+            Error::Frame(byte::Error::BadInput {
+                err: "Cannot decode 802.15.4 frame",
+            })
+        })?;
+
+        Ok(Message {
+            rx_time,
+            rx_quality,
+            frame,
+        })
+    }
+
     /// DW3000 User Manual 4.7.1
     /// returns dBm
     #[cfg(feature = "rssi")]
@@ -471,6 +542,7 @@ where
                     .rxovrr(0b1) // Receiver Overrun
                     .rxpto(0b1) // Preamble Detection Timeout
                     .rxsto(0b1) // Receiver SFD Timeout
+                    .arfe(0b1) // Automatic Frame Filtering rejection
                     .rxprej(0b1) // Receiver Preamble Rejection
             })
             .await?;
@@ -485,8 +557,9 @@ where
     /// no-op. If the receive operation is still ongoing, it will be aborted.
     #[maybe_async_attr]
     pub async fn finish_receiving(mut self) -> Result<DW3000<SPI, Ready>, (Self, Error<SPI>)> {
-        // TO DO : if we are not in state 3 (IDLE), we need to have a reset of the module (with a new initialisation)
-        // BECAUSE : using force_idle (fast command 0) is not puting the pll back to stable !!!
+        // NOTE: `force_idle` doesn't always bring the PLL back to a stable
+        // state. If the radio misbehaves afterwards, use `DW3000::recover_with` to
+        // reset and reconfigure it.
 
         if !self.state.is_finished() {
             match self.clear_status().await {
@@ -507,3 +580,64 @@ where
         })
     }
 }
+
+impl<SPI> DW3000<SPI, SingleBufferReceiving>
+where
+    SPI: spi_type::spi::SpiDevice<u8>,
+{
+    /// Waits for an acknowledgement with the sequence number `seq`
+    ///
+    /// `sent` is the TX timestamp of the frame that requested it. Other frames
+    /// and reception errors don't end the wait, the receiver is enabled again
+    /// for the rest of the frame wait timeout, counted from `sent`.
+    ///
+    /// Returns the frame pending bit of the acknowledgement, or `None` if the
+    /// receiver timed out.
+    #[maybe_async_attr]
+    pub(crate) async fn wait_for_ack(
+        &mut self,
+        seq: u8,
+        sent: Instant,
+    ) -> Result<Option<bool>, Error<SPI>> {
+        let mut buffer = [0_u8; 127];
+        loop {
+            match self.r_wait(&mut buffer).await {
+                Ok(message) => {
+                    let frame = message.frame;
+                    if frame.frame_type() == Ieee802154FrameType::Acknowledgement
+                        && frame.sequence_number() == Some(seq)
+                    {
+                        return Ok(Some(frame.frame_pending()));
+                    }
+                }
+                Err(nb::Error::WouldBlock) => continue,
+                Err(nb::Error::Other(Error::Spi(error))) => return Err(Error::Spi(error)),
+                // The receiver has turned itself off, the ACK window is over
+                Err(nb::Error::Other(
+                    Error::FrameWaitTimeout | Error::PreambleDetectionTimeout,
+                )) => return Ok(None),
+                // A frame that isn't an IEEE 802.15.4 frame
+                Err(nb::Error::Other(Error::Frame(_))) => (),
+                // Reception errors leave the status set and the receiver in an
+                // unknown state
+                Err(nb::Error::Other(_)) => {
+                    self.clear_status().await?;
+                    self.force_idle().await?;
+                }
+            }
+
+            // Not the acknowledgement, listen for the rest of the window
+            let mut config = self.state.config;
+            if let Some(timeout) = config.rx_frame_wait_timeout {
+                let elapsed = self.sys_time().await?.duration_since(sent);
+                if elapsed.value() >= timeout.value() {
+                    return Ok(None);
+                }
+                config.rx_frame_wait_timeout = Duration::new(timeout.value() - elapsed.value());
+            }
+            self.set_rx_timeouts(&config).await?;
+            self.fast_cmd(FastCommand::CMD_RX).await?;
+            self.state.finished = false;
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use super::Awake;
+use crate::{maybe_async_attr, spi_type, Config, Error, Event, Ready, Uninitialized, DW3000};
+
+#[cfg(not(feature = "async"))]
+use embedded_hal::delay::DelayNs;
+#[cfg(feature = "async")]
+use embedded_hal_async::delay::DelayNs;
+
+/// The settings that [`DW3000::recover_with`] restores after a reset
+///
+/// Everything else is either applied by [`DW3000::config`] or by the
+/// send and receive methods.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RadioSettings {
+    /// The RX antenna delay
+    pub rx_antenna_delay: u16,
+    /// The TX antenna delay
+    pub tx_antenna_delay: u16,
+    /// The PAN identifier
+    pub pan_id: u16,
+    /// The short address
+    pub short_addr: u16,
+    /// The EUI-64
+    pub eui: u64,
+    /// The events that drive the IRQ pin
+    pub interrupt_mask: Event,
+    /// The crystal trim
+    pub xtal_trim: u8,
+}
+
+impl<SPI, State> DW3000<SPI, State>
+where
+    SPI: spi_type::spi::SpiDevice<u8>,
+    State: Awake,
+{
+    /// Resets the digital parts of the DW3000
+    ///
+    /// The radio is back in its power-on state, and needs to be initialized
+    /// and configured again. Settings in the always-on memory are kept.
+    #[maybe_async_attr]
+    pub async fn soft_reset<DELAY>(
+        self,
+        mut delay_ns: DELAY,
+    ) -> Result<DW3000<SPI, Uninitialized>, Error<SPI>>
+    where
+        DELAY: DelayNs,
+    {
+        let mut radio = self.into_uninitialized();
+        radio.reset(&mut delay_ns).await?;
+
+        Ok(radio)
+    }
+
+    /// Reads the settings that [`DW3000::recover_with`] restores
+    ///
+    /// Keep the result from a working radio, to recover it later.
+    #[maybe_async_attr]
+    pub async fn read_settings(&mut self) -> Result<RadioSettings, Error<SPI>> {
+        let panadr = self.ll.panadr().read().await?;
+
+        Ok(RadioSettings {
+            rx_antenna_delay: self.ll.cia_conf().read().await?.rxantd(),
+            tx_antenna_delay: self.ll.tx_antd().read().await?.value(),
+            pan_id: panadr.pan_id(),
+            short_addr: panadr.short_addr(),
+            eui: self.ll.eui().read().await?.value(),
+            interrupt_mask: self.interrupt_mask().await?,
+            xtal_trim: self.get_xtal_trim().await?,
+        })
+    }
+
+    /// Writes settings read by [`DW3000::read_settings`]
+    #[maybe_async_attr]
+    pub async fn restore_settings(&mut self, settings: RadioSettings) -> Result<(), Error<SPI>> {
+        self.ll
+            .cia_conf()
+            .modify(|_, w| w.rxantd(settings.rx_antenna_delay))
+            .await?;
+        self.ll
+            .tx_antd()
+            .write(|w| w.value(settings.tx_antenna_delay))
+            .await?;
+        self.ll
+            .panadr()
+            .write(|w| w.pan_id(settings.pan_id).short_addr(settings.short_addr))
+            .await?;
+        self.ll.eui().write(|w| w.value(settings.eui)).await?;
+        self.set_interrupt_mask(settings.interrupt_mask).await?;
+        self.set_xtal_trim(settings.xtal_trim).await?;
+
+        Ok(())
+    }
+
+    /// Brings the radio back to a configured `Ready` state
+    ///
+    /// Use this when the radio is wedged, for example after the PLL lost its
+    /// lock, an RX overrun, or SPI errors. The radio is reset, initialized and
+    /// configured with `config`, and `settings` are restored.
+    ///
+    /// `settings` should be read with [`DW3000::read_settings`] while the
+    /// radio still works, for example right after configuring it, as a wedged
+    /// radio may not read them back correctly.
+    ///
+    /// On error, the radio is returned uninitialized, so recovering can be
+    /// tried again.
+    #[allow(clippy::type_complexity)]
+    #[maybe_async_attr]
+    pub async fn recover_with<DELAY>(
+        self,
+        settings: RadioSettings,
+        config: Config,
+        mut delay_ns: DELAY,
+    ) -> Result<DW3000<SPI, Ready>, (DW3000<SPI, Uninitialized>, Error<SPI>)>
+    where
+        DELAY: DelayNs,
+    {
+        let mut radio = self.into_uninitialized();
+
+        if let Err(error) = radio.reset(&mut delay_ns).await {
+            return Err((radio, error));
+        }
+        if let Err(error) = radio.init_registers().await {
+            return Err((radio, error));
+        }
+        if let Err(error) = radio.configure(config, &mut delay_ns).await {
+            return Err((radio, error));
+        }
+        if let Err(error) = radio.restore_settings(settings).await {
+            return Err((radio, error));
+        }
+
+        Ok(DW3000 {
+            ll: radio.ll,
+            seq: radio.seq,
+            state: Ready,
+        })
+    }
+
+    /// Best-effort variant of [`DW3000::recover_with`], which reads the
+    /// settings from the wedged radio first
+    ///
+    /// The settings are read over the same link from the same radio that
+    /// misbehaves, so they may be wrong. If they can't be read at all, the
+    /// radio is returned uninitialized and the settings are lost. Prefer
+    /// [`DW3000::recover_with`] with settings saved while the radio worked.
+    #[allow(clippy::type_complexity)]
+    #[maybe_async_attr]
+    pub async fn recover<DELAY>(
+        mut self,
+        config: Config,
+        delay_ns: DELAY,
+    ) -> Result<DW3000<SPI, Ready>, (DW3000<SPI, Uninitialized>, Error<SPI>)>
+    where
+        DELAY: DelayNs,
+    {
+        match self.read_settings().await {
+            Ok(settings) => self.recover_with(settings, config, delay_ns).await,
+            Err(error) => Err((self.into_uninitialized(), error)),
+        }
+    }
+
+    fn into_uninitialized(self) -> DW3000<SPI, Uninitialized> {
+        DW3000 {
+            ll: self.ll,
+            seq: self.seq,
+            state: Uninitialized,
+        }
+    }
+}
+
+impl<SPI> DW3000<SPI, Uninitialized>
+where
+    SPI: spi_type::spi::SpiDevice<u8>,
+{
+    /// Runs the soft reset sequence
+    #[maybe_async_attr]
+    async fn reset<DELAY>(&mut self, delay_ns: &mut DELAY) -> Result<(), Error<SPI>>
+    where
+        DELAY: DelayNs,
+    {
+        // The PLL is turned off by the reset, so switch to the crystal
+        // oscillator first
+        self.ll.clk_ctrl().modify(|_, w| w.sys_clk(0b01)).await?;
+
+        // Hold all blocks in reset, then release them
+        self.ll.soft_rst().write(|w| w).await?;
+        delay_ns.delay_ms(1).await;
+        self.ll
+            .soft_rst()
+            .write(|w| {
+                w.arm_rst(1)
+                    .prgn_rst(1)
+                    .cia_rst(1)
+                    .bist_rst(1)
+                    .rx_rst(1)
+                    .tx_rst(1)
+                    .hif_rst(1)
+                    .pmsc_rst(1)
+                    .gpio_rst(1)
+            })
+            .await?;
+
+        // Give the DW3000 time to get back to IDLE_RC
+        delay_ns.delay_ms(1).await;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use embedded_hal_mock::eh1::spi::{Mock as SpiMock, Transaction as SpiTransaction};
+
+    /// `embedded-hal-mock` has no asynchronous delay
+    struct NoDelay;
+
+    #[cfg(not(feature = "async"))]
+    impl DelayNs for NoDelay {
+        fn delay_ns(&mut self, _ns: u32) {}
+    }
+
+    #[cfg(feature = "async")]
+    impl DelayNs for NoDelay {
+        async fn delay_ns(&mut self, _ns: u32) {}
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(all(feature = "async"), tokio::test))]
+    async fn test_soft_reset() {
+        let spi = SpiMock::new(&[
+            // Switch the system clock to the crystal oscillator
+            SpiTransaction::transaction_start(),
+            SpiTransaction::transfer_in_place(
+                vec![0x62, 0x10, 0, 0, 0, 0],
+                vec![0x62, 0x10, 0x00, 0x02, 0x20, 0x07],
+            ),
+            SpiTransaction::transaction_end(),
+            SpiTransaction::transaction_start(),
+            SpiTransaction::write_vec(vec![0xE2, 0x10, 0x01, 0x02, 0x20, 0x07]),
+            SpiTransaction::transaction_end(),
+            // Assert and release the reset
+            SpiTransaction::transaction_start(),
+            SpiTransaction::write_vec(vec![0xE2, 0x00, 0x00, 0x00]),
+            SpiTransaction::transaction_end(),
+            SpiTransaction::transaction_start(),
+            SpiTransaction::write_vec(vec![0xE2, 0x00, 0xFF, 0x01]),
+            SpiTransaction::transaction_end(),
+        ]);
+
+        let dw3000 = DW3000::new_ready(spi);
+        let dw3000 = dw3000.soft_reset(NoDelay).await.unwrap();
+
+        let mut spi = dw3000.ll.spi;
+        spi.done();
+    }
+}
@@ -0,0 +1,107 @@
+use super::Awake;
+use crate::{maybe_async_attr, spi_type, Error, DW3000};
+
+/// The OTP address of the SAR voltage reading at 3.0 V
+const OTP_VBAT_ADDR: u16 = 0x08;
+
+/// The OTP address of the SAR temperature reading at 20 °C
+const OTP_TEMP_ADDR: u16 = 0x09;
+
+/// Converts a raw SAR temperature reading to °C
+///
+/// `reference` is the reading at 20 °C from the OTP memory.
+pub(crate) fn convert_temperature(raw: u8, reference: u8) -> f32 {
+    (raw as f32 - reference as f32) * 1.05 + 20.0
+}
+
+/// Converts a raw SAR voltage reading to V
+///
+/// `reference` is the reading at 3.0 V from the OTP memory. Without it, the
+/// nominal scale of the SAR (0.4 V reference over 255 steps, 16x attenuation)
+/// is used.
+pub(crate) fn convert_voltage(raw: u8, reference: Option<u8>) -> f32 {
+    const VOLTS_PER_LSB: f32 = 0.4 * 16.0 / 255.0;
+
+    match reference {
+        Some(reference) => (raw as f32 - reference as f32) * VOLTS_PER_LSB + 3.0,
+        None => raw as f32 * VOLTS_PER_LSB,
+    }
+}
+
+impl<SPI, State> DW3000<SPI, State>
+where
+    SPI: spi_type::spi::SpiDevice<u8>,
+    State: Awake,
+{
+    /// Runs a SAR conversion and returns the raw voltage and temperature
+    /// readings
+    #[maybe_async_attr]
+    pub async fn read_sar(&mut self) -> Result<(u8, u8), Error<SPI>> {
+        // Enable the temperature sensor and start the conversion
+        self.ll.rsar_test().modify(|_, w| w.sar_rden(1)).await?;
+        self.ll.sar_ctrl().write(|w| w.sar_start(1)).await?;
+
+        // A conversion takes a few microseconds
+        let mut done = false;
+        for _ in 0..1000 {
+            if self.ll.sar_status().read().await?.sar_done() == 1 {
+                done = true;
+                break;
+            }
+        }
+
+        let reading = self.ll.sar_reading().read().await?;
+
+        self.ll.sar_ctrl().write(|w| w.sar_start(0)).await?;
+        self.ll.rsar_test().modify(|_, w| w.sar_rden(0)).await?;
+
+        if !done {
+            return Err(Error::SarTimeout);
+        }
+
+        Ok((reading.sar_lvbat(), reading.sar_ltemp()))
+    }
+
+    /// Measures the chip temperature in °C
+    ///
+    /// Uses the calibration reference in the OTP memory, and returns
+    /// [`Error::NotCalibrated`] if it wasn't programmed.
+    #[maybe_async_attr]
+    pub async fn read_temperature(&mut self) -> Result<f32, Error<SPI>> {
+        let reference = self.read_otp(OTP_TEMP_ADDR).await? as u8;
+        if reference == 0 {
+            return Err(Error::NotCalibrated);
+        }
+        let (_, raw) = self.read_sar().await?;
+
+        Ok(convert_temperature(raw, reference))
+    }
+
+    /// Measures the supply voltage in V
+    ///
+    /// Uses the calibration reference in the OTP memory, if it was
+    /// programmed.
+    #[maybe_async_attr]
+    pub async fn read_voltage(&mut self) -> Result<f32, Error<SPI>> {
+        let reference = self.read_otp(OTP_VBAT_ADDR).await? as u8;
+        let (raw, _) = self.read_sar().await?;
+
+        Ok(convert_voltage(raw, Some(reference).filter(|&r| r != 0)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn conversions() {
+        assert_eq!(convert_temperature(0x85, 0x85), 20.0);
+        assert_eq!(convert_temperature(0x8F, 0x85), 30.5);
+        assert_eq!(convert_temperature(0x80, 0x85), 14.75);
+
+        assert_eq!(convert_voltage(0x74, Some(0x74)), 3.0);
+        assert!((convert_voltage(0x6E, Some(0x74)) - 2.8494).abs() < 0.001);
+        assert!((convert_voltage(51, None) - 1.28).abs() < 0.001);
+    }
+}
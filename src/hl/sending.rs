@@ -59,6 +59,18 @@ where
             .await
             .map_err(|error| nb::Error::Other(Error::Spi(error)))?;
 
+        // Was the frame suppressed by the clear channel assessment?
+        if sys_status.cca_fail() == 0b1 {
+            self.finish_cca().await.map_err(nb::Error::Other)?;
+            self.ll
+                .sys_status()
+                .write(|w| w.cca_fail(0b1))
+                .await
+                .map_err(|error| nb::Error::Other(Error::Spi(error)))?;
+
+            return Err(nb::Error::Other(Error::ChannelBusy));
+        }
+
         // Has the frame been sent?
         if sys_status.txfrs() == 0b0 {
             // Frame has not been sent
@@ -66,6 +78,7 @@ where
         }
 
         // Frame sent
+        self.finish_cca().await.map_err(nb::Error::Other)?;
         self.reset_flags().await.map_err(nb::Error::Other)?;
         self.state.mark_finished();
 
@@ -88,6 +101,29 @@ where
         }
     }
 
+    /// Wait for the transmission to finish, sleeping on the IRQ pin
+    ///
+    /// Unlike [`DW3000::s_wait`], this doesn't poll `SYS_STATUS` over SPI.
+    /// It is only read once before waiting and again whenever `irq` goes
+    /// high. Call [`DW3000::enable_tx_interrupts`] before sending, and keep
+    /// other interrupts disabled, so the pin only rises when the transmission
+    /// is over.
+    #[cfg(feature = "async")]
+    pub async fn wait_sent<IRQ>(&mut self, irq: &mut IRQ) -> Result<Instant, Error<SPI>>
+    where
+        IRQ: embedded_hal_async::digital::Wait,
+    {
+        loop {
+            match self.s_wait().await {
+                Ok(tx_time) => return Ok(tx_time),
+                Err(nb::Error::Other(error)) => return Err(error),
+                Err(nb::Error::WouldBlock) => {}
+            }
+
+            irq.wait_for_high().await.map_err(|_| Error::Irq)?;
+        }
+    }
+
     /// Finishes sending and returns to the `Ready` state.
     ///
     /// If the used tx continuation was not set to ready, this function returns an error.
@@ -162,6 +198,22 @@ where
         })
     }
 
+    /// Restores the preamble detection timeout after a clear channel
+    /// assessment, so it doesn't affect later receive operations
+    #[maybe_async_attr]
+    async fn finish_cca(&mut self) -> Result<(), Error<SPI>> {
+        if self.state.cca {
+            let pre_toc = match self.state.continuation {
+                TxContinuation::Rx => self.state.config.rx_preamble_timeout.unwrap_or(0),
+                TxContinuation::Ready => 0,
+            };
+            self.ll.pre_toc().write(|w| w.value(pre_toc)).await?;
+            self.state.cca = false;
+        }
+
+        Ok(())
+    }
+
     #[maybe_async_attr]
     async fn reset_flags(&mut self) -> Result<(), Error<SPI>> {
         self.ll
@@ -14,6 +14,8 @@ pub struct Sending {
     pub(super) finished: bool,
     pub(super) continuation: TxContinuation,
     pub(super) config: Config,
+    /// The transmission was started by a clear channel assessment
+    pub(super) cca: bool,
 }
 impl Sending {
     /// Mark the receiving state as finished
@@ -43,6 +45,23 @@ pub struct AutoDoubleBufferReceiving {
     pub(super) config: Config,
 }
 
+/// Indicates that the `DW3000` instance is in a transmitter test mode
+///
+/// This is used for regulatory testing, see
+/// [`DW3000::start_continuous_wave`] and [`DW3000::start_continuous_frame`].
+///
+/// [`DW3000::start_continuous_wave`]: super::DW3000::start_continuous_wave
+/// [`DW3000::start_continuous_frame`]: super::DW3000::start_continuous_frame
+#[derive(Debug)]
+pub struct TestMode {
+    /// The registers that entering the test mode changed, restored when
+    /// leaving it
+    pub(super) rf_enable: u32,
+    pub(super) rf_ctrl_mask: u32,
+    pub(super) ldo_ctrl: u32,
+    pub(super) clk_ctrl: [u8; 4],
+}
+
 /// Indicates that the `DW3000` instance is currently sleeping
 #[derive(Debug)]
 pub struct Sleeping {
@@ -58,6 +77,7 @@ impl Awake for Ready {}
 impl Awake for Sending {}
 impl Awake for SingleBufferReceiving {}
 impl Awake for AutoDoubleBufferReceiving {}
+impl Awake for TestMode {}
 /// Any state struct that implements this trait signals that the radio is
 /// sleeping.
 pub trait Asleep {}
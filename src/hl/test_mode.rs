@@ -0,0 +1,199 @@
+#![allow(unused_imports)]
+
+use crate::{ll, maybe_async_attr, spi_type, Error, Ready, TestMode, DW3000};
+
+impl<SPI> DW3000<SPI, TestMode>
+where
+    SPI: spi_type::spi::SpiDevice<u8>,
+{
+    /// Leaves the test mode and returns to the `Ready` state
+    ///
+    /// Stops the continuous wave or continuous frame transmission, and
+    /// restores the RF enables, LDO control and clock selection that were in
+    /// place before the test mode was entered, so normal sending and
+    /// receiving work again.
+    #[allow(clippy::type_complexity)]
+    #[maybe_async_attr]
+    pub async fn finish_test_mode(mut self) -> Result<DW3000<SPI, Ready>, (Self, Error<SPI>)> {
+        match self.disable_test_mode().await {
+            Ok(()) => (),
+            Err(error) => return Err((self, error)),
+        }
+
+        Ok(DW3000 {
+            ll: self.ll,
+            seq: self.seq,
+            state: Ready,
+        })
+    }
+
+    #[maybe_async_attr]
+    async fn disable_test_mode(&mut self) -> Result<(), Error<SPI>> {
+        // Stop the continuous frame mode first, so no new frame gets started
+        self.ll.diag_tmc().modify(|_, w| w.tx_pstm(0b0)).await?;
+        self.ll.tx_test().write(|w| w.tx_entest(0x0)).await?;
+
+        self.force_idle().await?;
+
+        // Restore the registers that were changed when entering the test mode
+        let state = &self.state;
+        self.ll
+            .rf_enable()
+            .write(|w| w.value(state.rf_enable))
+            .await?;
+        self.ll
+            .rf_ctrl_mask()
+            .write(|w| w.value(state.rf_ctrl_mask))
+            .await?;
+        self.ll
+            .ldo_ctrl()
+            .write(|w| {
+                w.low(state.ldo_ctrl as u16)
+                    .high((state.ldo_ctrl >> 16) as u16)
+            })
+            .await?;
+        self.ll
+            .clk_ctrl()
+            .write(|w| {
+                let buffer = <ll::CLK_CTRL as ll::Writable>::buffer(w);
+                let len = buffer.len();
+                buffer[len - 4..].copy_from_slice(&state.clk_ctrl);
+                w
+            })
+            .await?;
+
+        // Clear the events of the test transmissions
+        self.ll
+            .sys_status()
+            .write(|w| w.txfrb(0b1).txprs(0b1).txphs(0b1).txfrs(0b1))
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use embedded_hal_mock::eh1::spi::{Mock as SpiMock, Transaction as SpiTransaction};
+
+    use crate::{
+        configs::UwbChannel,
+        test_utils::{fast_command, read, write},
+        time::Duration,
+        FastCommand,
+    };
+
+    const RF_ENABLE: [u8; 4] = [0x01, 0x00, 0x00, 0x00];
+    const RF_CTRL_MASK: [u8; 4] = [0x02, 0x00, 0x00, 0x00];
+    const LDO_CTRL: [u8; 4] = [0x05, 0x01, 0x00, 0x00];
+    const CLK_CTRL: [u8; 4] = [0x00, 0x02, 0x20, 0x07];
+
+    /// Enabling the transmitter on channel 5, which saves the registers
+    fn enable_rf_tx() -> Vec<SpiTransaction<u8>> {
+        [
+            read(0x07, 0x00, &RF_ENABLE),
+            write(0x07, 0x00, &[0x01, 0x3C, 0x00, 0x02]),
+            read(0x07, 0x04, &RF_CTRL_MASK),
+            write(0x07, 0x04, &[0x02, 0x3C, 0x00, 0x02]),
+            read(0x07, 0x48, &LDO_CTRL),
+            write(0x07, 0x48, &[0x65, 0x09, 0x60, 0x08]),
+            read(0x11, 0x04, &CLK_CTRL),
+            write(0x11, 0x04, &[0x22, 0x02, 0x20, 0x07]),
+        ]
+        .concat()
+    }
+
+    /// Leaving the test mode, which restores the registers
+    fn disable_test_mode() -> Vec<SpiTransaction<u8>> {
+        [
+            read(0x0F, 0x24, &[0x10, 0, 0, 0]),
+            write(0x0F, 0x24, &[0, 0, 0, 0]),
+            write(0x07, 0x28, &[0]),
+            fast_command(FastCommand::CMD_TXRXOFF),
+            write(0x07, 0x00, &RF_ENABLE),
+            write(0x07, 0x04, &RF_CTRL_MASK),
+            write(0x07, 0x48, &LDO_CTRL),
+            write(0x11, 0x04, &CLK_CTRL),
+            write(0x00, 0x44, &[0xF0, 0, 0, 0, 0, 0]),
+        ]
+        .concat()
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(all(feature = "async"), tokio::test))]
+    async fn test_continuous_wave() {
+        let expectations = [
+            // Channel 5 is configured
+            read(0x01, 0x14, &[0; 2]),
+            fast_command(FastCommand::CMD_TXRXOFF),
+            enable_rf_tx(),
+            write(0x07, 0x28, &[0x0F]),
+            disable_test_mode(),
+        ]
+        .concat();
+
+        let spi = SpiMock::new(&expectations);
+        let dw3000 = DW3000::new_ready(spi);
+
+        let dw3000 = dw3000
+            .start_continuous_wave(UwbChannel::Channel5)
+            .await
+            .unwrap();
+        let mut dw3000 = dw3000.finish_test_mode().await.unwrap();
+
+        dw3000.ll().bus().done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(all(feature = "async"), tokio::test))]
+    async fn test_continuous_frame() {
+        let frame = [0x41, 0x88, 0x00];
+        let mut tx_buffer = [0; 127];
+        tx_buffer[..3].copy_from_slice(&frame);
+
+        let expectations = [
+            // Channel 5 is configured
+            read(0x01, 0x14, &[0; 2]),
+            fast_command(FastCommand::CMD_TXRXOFF),
+            enable_rf_tx(),
+            // Frame and CRC
+            write(0x14, 0x00, &tx_buffer),
+            read(0x00, 0x24, &[0; 6]),
+            write(0x00, 0x24, &[5, 0, 0, 0, 0, 0]),
+            // Repetition period of 1 ms
+            write(0x00, 0x2C, &[0x80, 0xE7, 0x01, 0x00]),
+            read(0x0F, 0x24, &[0; 4]),
+            write(0x0F, 0x24, &[0x10, 0, 0, 0]),
+            fast_command(FastCommand::CMD_TX),
+            disable_test_mode(),
+        ]
+        .concat();
+
+        let spi = SpiMock::new(&expectations);
+        let dw3000 = DW3000::new_ready(spi);
+
+        let dw3000 = dw3000
+            .start_continuous_frame(&frame, Duration::from_nanos(1_000_000))
+            .await
+            .unwrap();
+        let mut dw3000 = dw3000.finish_test_mode().await.unwrap();
+
+        dw3000.ll().bus().done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(all(feature = "async"), tokio::test))]
+    async fn test_continuous_frame_too_long() {
+        // With the CRC, the frame takes up 128 bytes
+        let frame = [0; 126];
+
+        let mut spi = SpiMock::new(&[]);
+        let dw3000 = DW3000::new_ready(spi.clone());
+
+        let result = dw3000
+            .start_continuous_frame(&frame, Duration::from_nanos(1_000_000))
+            .await;
+        assert!(matches!(result, Err(Error::FrameTooLong)));
+
+        spi.done();
+    }
+}
@@ -29,27 +29,26 @@ where
         }
     }
 
-    /// Read the OTP memory at the given address
-    #[maybe_async_attr]
-    pub async fn read_otp(&mut self, addr: u16) -> Result<u32, ll::Error<SPI>> {
-        // Set OTP_MAN to 1
-        self.ll.otp_cfg().write(|w| w.otp_man(1)).await?;
-        // Set the 10-bit address
-        self.ll.otp_addr().modify(|_, w| w.otp_addr(addr)).await?;
-        // Set OTP_READ to 1
-        self.ll.otp_cfg().write(|w| w.otp_read(1)).await?;
-        // Read the data (32 bits)
-        let data = self.ll.otp_rdata().read().await?.value();
-        Ok(data)
-    }
-
     /// Initialize the DW3000
     ///
     /// Basicaly, this is the pll configuration. We want to have a locked pll in order to provide a constant speed clock.
     /// This is important when using th clock to measure distances.
     /// At the end of this function, pll is locked and it can be checked by the bit CPLOCK in SYS_STATUS register
+    ///
+    /// The factory calibration in the OTP memory (see [`OtpData`](crate::OtpData)) is applied,
+    /// as well as the EUI if it was programmed. Antenna delays in the OTP
+    /// memory are only applied by [`DW3000::apply_otp_antenna_delay`].
     #[maybe_async_attr]
     pub async fn init(mut self) -> Result<DW3000<SPI, Uninitialized>, Error<SPI>> {
+        self.init_registers().await?;
+
+        Ok(self)
+    }
+
+    /// Runs the initialization of [`DW3000::init`] without consuming the
+    /// driver
+    #[maybe_async_attr]
+    pub(super) async fn init_registers(&mut self) -> Result<(), Error<SPI>> {
         // Wait for the INIT_RC state
         for _ in 0..1000 {
             if self.ll.sys_status().read().await?.rcinit() == 1 {
@@ -69,57 +68,38 @@ where
             return Err(Error::InvalidConfiguration);
         }
 
-        // Read LDO_TUNE value from OTP memory
-        let ldo_tune_l = self.read_otp(0x04).await?;
-        let ldo_tune_h = self.read_otp(0x05).await?;
-
-        // Read BIASTUNE_CAL value from OTP memory (bit 16 to 20)
-        let biastune_cal = (self.read_otp(0x0A).await? >> 0x10) & 0x1F;
+        let otp = self.read_otp_data().await?;
 
         #[cfg(feature = "defmt")]
-        defmt::trace!(
-            "LDO_TUNE_L = {:x}, LDO_TUNE_H = {:x}, BIASTUNE_CAL = {:x}",
-            ldo_tune_l,
-            ldo_tune_h,
-            biastune_cal
-        );
+        defmt::trace!("OTP = {}", otp);
 
         // Set LDO_TUNE and BIASTUNE_CAL values if OTP memory is valid
-        if ldo_tune_l != 0 && ldo_tune_h != 0 && (biastune_cal != 0) {
+        if let (Some(_), Some(bias_tune)) = (otp.ldo_tune, otp.bias_tune) {
             self.ll()
                 .otp_cfg()
                 .write(|w| w.ldo_kick(1).bias_kick(1))
                 .await?;
             self.ll()
                 .bias_ctrl()
-                .modify(|r, w| w.value(r.value() & 0xFFE0 | biastune_cal as u16))
+                .modify(|r, w| w.value(r.value() & 0xFFE0 | bias_tune as u16))
                 .await?
         }
 
         // Configuration of `XTAL_TRIM`
-        self.ll.otp_cfg().modify(|_, w| w.otp_man(1)).await?;
-        self.ll.otp_addr().modify(|_, w| w.otp_addr(0x1E)).await?;
-        self.ll.otp_cfg().modify(|_, w| w.otp_read(1)).await?;
-        let xtrim = self.ll.otp_rdata().read().await?.value() & 0x3F;
-
-        if xtrim != 0 {
-            self.ll.xtal().modify(|_, w| w.value(xtrim as u8)).await?;
-        } else {
-            self.ll.xtal().modify(|_, w| w.value(0x2E)).await?;
-        }
+        let xtal_trim = otp.xtal_trim.unwrap_or(0x2E);
+        self.ll.xtal().modify(|_, w| w.value(xtal_trim)).await?;
 
         // Load the PLL code
-        let pll_lock_code = self.read_otp(0x35).await?;
-
-        if pll_lock_code != 0 {
+        if let Some(pll_lock_code) = otp.pll_lock_code {
             self.ll.pll_cc().write(|w| w.value(pll_lock_code)).await?;
         }
 
-        Ok(DW3000 {
-            ll: self.ll,
-            seq: self.seq,
-            state: Uninitialized,
-        })
+        // Use the programmed EUI, if any
+        if let Some(eui) = otp.eui {
+            self.ll.eui().write(|w| w.value(eui)).await?;
+        }
+
+        Ok(())
     }
 
     /// Configuration of the DW3000, need to be called after an init.
@@ -129,6 +109,26 @@ where
         config: Config,
         mut delay_ns: DELAY,
     ) -> Result<DW3000<SPI, Ready>, Error<SPI>>
+    where
+        DELAY: DelayNs,
+    {
+        self.configure(config, &mut delay_ns).await?;
+
+        Ok(DW3000 {
+            ll: self.ll,
+            seq: self.seq,
+            state: Ready,
+        })
+    }
+
+    /// Runs the configuration of [`DW3000::config`] without consuming the
+    /// driver
+    #[maybe_async_attr]
+    pub(super) async fn configure<DELAY>(
+        &mut self,
+        config: Config,
+        delay_ns: &mut DELAY,
+    ) -> Result<(), Error<SPI>>
     where
         DELAY: DelayNs,
     {
@@ -197,7 +197,7 @@ where
 
         delay_ns.delay_us(20).await;
 
-        let pgf_cal_result = self.run_pgf_cal_async(delay_ns).await;
+        let pgf_cal_result = self.run_pgf_cal_async(&mut *delay_ns).await;
 
         self.ll
             .ldo_ctrl()
@@ -205,11 +205,7 @@ where
             .await?; // restore LDO_CTRL
         pgf_cal_result?;
 
-        Ok(DW3000 {
-            ll: self.ll,
-            seq: self.seq,
-            state: Ready,
-        })
+        Ok(())
     }
 
     /// Run the PGF calibration, async version
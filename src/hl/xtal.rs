@@ -0,0 +1,275 @@
+use super::{Awake, ReceiveTime};
+use crate::{
+    maybe_async_attr, spi_type, time::Duration, Config, Error, Ready, SingleBufferReceiving, DW3000,
+};
+
+/// The largest crystal trim value
+pub const XTAL_TRIM_MAX: u8 = 0x3F;
+
+/// The approximate frequency change per crystal trim step, in ppm
+///
+/// The real sensitivity depends on the crystal and is not linear, which the
+/// tuning loop makes up for by measuring again after each step.
+const PPM_PER_TRIM_STEP: f32 = 1.5;
+
+/// The number of trim steps after which [`DW3000::tune_xtal`] gives up
+const MAX_TUNING_STEPS: usize = 16;
+
+/// The number of receptions per sample after which [`DW3000::tune_xtal`]
+/// gives up
+const ATTEMPTS_PER_SAMPLE: u32 = 4;
+
+/// How long a reception waits for a frame, if `config` doesn't set a frame
+/// wait timeout
+const DEFAULT_FRAME_WAIT_TIMEOUT_NANOS: u32 = 1_000_000_000;
+
+/// Converts a clock offset from [`DW3000::get_clock_offset`] to ppm
+pub(crate) fn clock_offset_to_ppm(offset: f32) -> f32 {
+    offset * 1e6 / (1 << 26) as f32
+}
+
+/// Returns the crystal trim that should bring the clock offset (in ppm) to 0
+///
+/// A positive offset means the local clock is slow, so the load capacitance
+/// is lowered.
+pub(crate) fn next_trim(trim: u8, offset_ppm: f32) -> Option<u8> {
+    let steps = offset_ppm / PPM_PER_TRIM_STEP;
+    // Round, and take at least one step
+    let steps = if steps >= 0.0 {
+        (steps + 0.5).max(1.0) as i16
+    } else {
+        (steps - 0.5).min(-1.0) as i16
+    };
+
+    let trim = trim as i16 - steps;
+    if (0..=XTAL_TRIM_MAX as i16).contains(&trim) {
+        Some(trim as u8)
+    } else {
+        None
+    }
+}
+
+impl<SPI, State> DW3000<SPI, State>
+where
+    SPI: spi_type::spi::SpiDevice<u8>,
+    State: Awake,
+{
+    /// Returns the crystal trim
+    #[maybe_async_attr]
+    pub async fn get_xtal_trim(&mut self) -> Result<u8, Error<SPI>> {
+        Ok(self.ll.xtal().read().await?.value() & XTAL_TRIM_MAX)
+    }
+
+    /// Sets the crystal trim
+    ///
+    /// Higher values lower the crystal frequency. `init` sets the trim that was
+    /// calibrated at the factory, which can be overwritten in the OTP memory
+    /// with [`DW3000::program_otp`] (address 0x1E), as long as no bits need to
+    /// be cleared.
+    #[maybe_async_attr]
+    pub async fn set_xtal_trim(&mut self, trim: u8) -> Result<(), Error<SPI>> {
+        if trim > XTAL_TRIM_MAX {
+            return Err(Error::InvalidConfiguration);
+        }
+
+        self.ll
+            .xtal()
+            .modify(|r, w| w.value(r.value() & !XTAL_TRIM_MAX | trim))
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl<SPI> DW3000<SPI, Ready>
+where
+    SPI: spi_type::spi::SpiDevice<u8>,
+{
+    /// Tunes the crystal trim against a reference node
+    ///
+    /// Receives `samples` frames with `config`, averages their clock offset
+    /// (see [`DW3000::get_clock_offset`]), and steps the crystal trim until
+    /// the average offset is within `target_ppm`. Use the frame filter of
+    /// `config` to only accept frames from the reference node. Frames with
+    /// reception errors, like a bad CRC or a timeout, are skipped. Other
+    /// errors, like an invalid `config`, are returned right away.
+    ///
+    /// Each reception waits for the frame wait timeout of `config`, or 1 s if
+    /// it has none, and a measurement gives up after 4 receptions per sample.
+    ///
+    /// Returns the radio and the remaining clock offset in ppm. The tuned trim
+    /// can be read with [`DW3000::get_xtal_trim`]. If the trim runs out of
+    /// range or doesn't converge, or not enough frames arrive,
+    /// [`Error::XtalTuningFailed`] is returned along with the radio.
+    #[allow(clippy::type_complexity)]
+    #[maybe_async_attr]
+    pub async fn tune_xtal(
+        mut self,
+        config: Config,
+        target_ppm: f32,
+        samples: u16,
+    ) -> Result<(Self, f32), (Self, Error<SPI>)> {
+        if samples == 0 {
+            return Err((self, Error::InvalidConfiguration));
+        }
+
+        for _ in 0..MAX_TUNING_STEPS {
+            let (radio, offset_ppm) = self.measure_clock_offset(config, samples).await?;
+            self = radio;
+
+            if offset_ppm.abs() <= target_ppm {
+                return Ok((self, offset_ppm));
+            }
+
+            let trim = match self.get_xtal_trim().await {
+                Ok(trim) => trim,
+                Err(error) => return Err((self, error)),
+            };
+            let trim = match next_trim(trim, offset_ppm) {
+                Some(trim) => trim,
+                None => return Err((self, Error::XtalTuningFailed)),
+            };
+
+            #[cfg(feature = "defmt")]
+            defmt::debug!("Clock offset {} ppm, XTAL trim {}", offset_ppm, trim);
+
+            match self.set_xtal_trim(trim).await {
+                Ok(()) => (),
+                Err(error) => return Err((self, error)),
+            }
+        }
+
+        Err((self, Error::XtalTuningFailed))
+    }
+
+    /// Returns the average clock offset of `samples` received frames in ppm
+    #[allow(clippy::type_complexity)]
+    #[maybe_async_attr]
+    async fn measure_clock_offset(
+        mut self,
+        mut config: Config,
+        samples: u16,
+    ) -> Result<(Self, f32), (Self, Error<SPI>)> {
+        // Don't wait forever if the reference node is silent
+        if config.rx_frame_wait_timeout.is_none() {
+            config.rx_frame_wait_timeout =
+                Some(Duration::from_nanos(DEFAULT_FRAME_WAIT_TIMEOUT_NANOS));
+        }
+
+        let mut buffer = [0; 127];
+        let mut sum = 0i32;
+        let mut count = 0;
+
+        for _ in 0..samples as u32 * ATTEMPTS_PER_SAMPLE {
+            let mut receiving = DW3000 {
+                ll: self.ll,
+                seq: self.seq,
+                state: SingleBufferReceiving {
+                    finished: false,
+                    config,
+                },
+            };
+
+            let result = match receiving.start_receiving(ReceiveTime::Now, config).await {
+                Ok(()) => loop {
+                    match receiving.r_wait_buf(&mut buffer).await {
+                        Err(nb::Error::WouldBlock) => continue,
+                        Err(nb::Error::Other(error)) => break Err(error),
+                        Ok(_) => break Ok(()),
+                    }
+                },
+                Err(error) => Err(error),
+            };
+
+            let offset = match result {
+                Ok(()) => receiving.get_clock_offset().await.map(Some),
+                Err(error) if is_reception_error(&error) => Ok(None),
+                Err(error) => Err(error),
+            };
+
+            self = match receiving.finish_receiving().await {
+                Ok(radio) => radio,
+                Err((receiving, error)) => return Err((into_ready(receiving), error)),
+            };
+
+            match offset {
+                Ok(Some(offset)) => {
+                    sum += offset as i32;
+                    count += 1;
+                }
+                Ok(None) => (),
+                Err(error) => return Err((self, error)),
+            }
+
+            if count == samples {
+                return Ok((self, clock_offset_to_ppm(sum as f32 / count as f32)));
+            }
+        }
+
+        Err((self, Error::XtalTuningFailed))
+    }
+}
+
+/// Whether a reception failed because of the frame or the channel, so the
+/// sample can be skipped
+fn is_reception_error<SPI>(error: &Error<SPI>) -> bool
+where
+    SPI: spi_type::spi::ErrorType,
+{
+    matches!(
+        error,
+        Error::Fcs
+            | Error::Phy
+            | Error::ReedSolomon
+            | Error::SfdTimeout
+            | Error::FrameFilteringRejection
+            | Error::FrameWaitTimeout
+            | Error::Overrun
+            | Error::PreambleDetectionTimeout
+    )
+}
+
+/// Gives the radio back after the receiver couldn't be turned off
+fn into_ready<SPI>(radio: DW3000<SPI, SingleBufferReceiving>) -> DW3000<SPI, Ready> {
+    DW3000 {
+        ll: radio.ll,
+        seq: radio.seq,
+        state: Ready,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use embedded_hal_mock::eh1::spi::Mock as SpiMock;
+
+    #[test]
+    fn clock_offset() {
+        assert_eq!(clock_offset_to_ppm(0.0), 0.0);
+        assert!((clock_offset_to_ppm(671.0) - 9.9987).abs() < 0.001);
+        assert!((clock_offset_to_ppm(-4096.0) + 61.035).abs() < 0.001);
+    }
+
+    #[test]
+    fn reception_errors() {
+        type Error = super::Error<SpiMock<u8>>;
+
+        assert!(is_reception_error(&Error::Fcs));
+        assert!(is_reception_error(&Error::FrameWaitTimeout));
+        assert!(is_reception_error(&Error::PreambleDetectionTimeout));
+        assert!(!is_reception_error(&Error::InvalidConfiguration));
+        assert!(!is_reception_error(&Error::RxNotFinished));
+    }
+
+    #[test]
+    fn trim_steps() {
+        // Slow clock, lower the trim
+        assert_eq!(next_trim(0x2E, 3.0), Some(0x2C));
+        assert_eq!(next_trim(0x2E, 0.2), Some(0x2D));
+        // Fast clock, raise the trim
+        assert_eq!(next_trim(0x2E, -1.0), Some(0x2F));
+        assert_eq!(next_trim(0x3F, -1.0), None);
+        assert_eq!(next_trim(0x01, 4.0), None);
+    }
+}
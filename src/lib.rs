@@ -7,6 +7,12 @@
 //! This driver is built on top of [`embedded-hal`], which means it is portable
 //! and can be used on any platform that implements the `embedded-hal` API.
 //!
+//! The `async` feature, which is enabled by default, switches the driver to
+//! `embedded-hal-async`. The `phy` module, a smoltcp device, is only available
+//! without it, as smoltcp's device API is blocking. For the same reason, the
+//! `gpio` handles only implement the `embedded_hal::digital` traits without
+//! it.
+//!
 //! [high-level interface]: hl/index.html
 //! [register-level interface]: ll/index.html
 //! [`embedded-hal`]: https://crates.io/crates/embedded-hal
@@ -23,12 +29,23 @@ use embedded_hal as spi_type;
 #[cfg(feature = "async")]
 use embedded_hal_async as spi_type;
 
+pub mod compensation;
 pub mod configs;
+#[cfg(feature = "embassy-net-driver")]
+pub mod embassy;
 pub mod fast_command;
+pub mod gpio;
 pub mod hl;
 pub mod ll;
+pub mod mac;
+#[cfg(not(feature = "async"))]
+pub mod phy;
+pub mod tdma;
 pub mod time;
 
+#[cfg(test)]
+mod test_utils;
+
 /// Redirection of nb::block
 pub mod block {
     pub use nb::block;
@@ -39,7 +56,7 @@ pub use crate::{
     configs::Config,
     fast_command::FastCommand,
     hl::{
-        AutoDoubleBufferReceiving, Error, Message, Ready, Sending, SingleBufferReceiving, Sleeping,
-        Uninitialized, DW3000,
+        AutoDoubleBufferReceiving, Error, Event, Message, OtpData, Ready, Sending,
+        SingleBufferReceiving, Sleeping, TestMode, Uninitialized, DW3000,
     },
 };
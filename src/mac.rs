@@ -0,0 +1,674 @@
+//! IEEE 802.15.4 MAC layer with unslotted CSMA-CA
+//!
+//! The [`Mac`] owns a [`DW3000`] in the `Ready` state and takes care of
+//! channel access, acknowledgements and retransmissions. Channel access follows
+//! the unslotted CSMA-CA algorithm of IEEE 802.15.4: before each transmission
+//! it waits for a random number of backoff periods, then the DW3000 performs a
+//! clear channel assessment by listening for a preamble and only transmits if
+//! it doesn't detect one.
+
+#![allow(unused_imports)]
+
+use core::fmt;
+
+use smoltcp::wire::{Ieee802154Address, Ieee802154Frame, Ieee802154FrameType};
+
+use crate::{
+    configs::TxContinuation,
+    hl::{Message, SendTime},
+    maybe_async_attr, spi_type,
+    time::{Duration, Instant},
+    Config, Error, Ready, DW3000,
+};
+
+/// The length of a backoff period, 20 symbols of about 1017.6 ns each
+const UNIT_BACKOFF_PERIOD_NANOS: u32 = 20 * 1018;
+
+/// Configuration of the [`Mac`]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MacConfig {
+    /// The radio configuration used for sending and receiving
+    pub radio: Config,
+    /// The minimum backoff exponent (macMinBE). Defaults to 3.
+    pub min_be: u8,
+    /// The maximum backoff exponent (macMaxBE), at most 8. Defaults to 5.
+    pub max_be: u8,
+    /// How often the backoff is repeated after a busy channel before the
+    /// transmission fails (macMaxCSMABackoffs). Defaults to 4.
+    pub max_csma_backoffs: u8,
+    /// How often an unacknowledged frame is sent again (macMaxFrameRetries).
+    /// Defaults to 3.
+    pub max_frame_retries: u8,
+    /// How long to wait for an acknowledgement after a transmission.
+    /// Defaults to 1 ms.
+    pub ack_wait_duration: Duration,
+    /// How long the clear channel assessment listens for a preamble, in PAC
+    /// units. Defaults to 8.
+    pub cca_duration: u16,
+}
+
+impl Default for MacConfig {
+    fn default() -> Self {
+        MacConfig {
+            radio: Default::default(),
+            min_be: 3,
+            max_be: 5,
+            max_csma_backoffs: 4,
+            max_frame_retries: 3,
+            ack_wait_duration: Duration::from_nanos(1_000_000),
+            cca_duration: 8,
+        }
+    }
+}
+
+/// An error that can occur in the [`Mac`]
+pub enum MacError<SPI>
+where
+    SPI: spi_type::spi::ErrorType,
+{
+    /// Error reported by the radio
+    Radio(Error<SPI>),
+    /// The channel was still busy after `max_csma_backoffs` backoffs
+    ChannelAccessFailure,
+    /// The frame was not acknowledged after `max_frame_retries` retries
+    NoAck,
+    /// An earlier error left the MAC without a usable radio
+    RadioLost,
+}
+
+impl<SPI> From<Error<SPI>> for MacError<SPI>
+where
+    SPI: spi_type::spi::ErrorType,
+{
+    fn from(error: Error<SPI>) -> Self {
+        MacError::Radio(error)
+    }
+}
+
+// We can't derive this implementation, as `Debug` is only implemented
+// conditionally for `ll::Debug`.
+impl<SPI> fmt::Debug for MacError<SPI>
+where
+    SPI: spi_type::spi::ErrorType,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MacError::Radio(error) => write!(f, "Radio({:?})", error),
+            MacError::ChannelAccessFailure => write!(f, "ChannelAccessFailure"),
+            MacError::NoAck => write!(f, "NoAck"),
+            MacError::RadioLost => write!(f, "RadioLost"),
+        }
+    }
+}
+
+/// IEEE 802.15.4 MAC with unslotted CSMA-CA and retransmissions
+///
+/// Errors reported by the radio while a frame is in flight can't be recovered
+/// from, as the driver consumes itself on each state transition. After such an
+/// error every call returns [`MacError::RadioLost`].
+pub struct Mac<SPI> {
+    radio: Option<DW3000<SPI, Ready>>,
+    config: MacConfig,
+    rng: u32,
+}
+
+impl<SPI> Mac<SPI>
+where
+    SPI: spi_type::spi::SpiDevice<u8>,
+{
+    /// Creates a MAC on top of an initialized and configured radio
+    pub fn new(radio: DW3000<SPI, Ready>, config: MacConfig) -> Self {
+        Mac {
+            radio: Some(radio),
+            config,
+            rng: 0x9E37_79B9,
+        }
+    }
+
+    /// Returns the radio, unless it was lost to an error
+    pub fn release(self) -> Option<DW3000<SPI, Ready>> {
+        self.radio
+    }
+
+    /// Sends `payload` to `dst` in the PAN of the radio
+    ///
+    /// Unless `dst` is the broadcast address, the frame requests an
+    /// acknowledgement and is sent again up to `max_frame_retries` times until
+    /// one arrives.
+    #[maybe_async_attr]
+    pub async fn send(
+        &mut self,
+        dst: Ieee802154Address,
+        payload: &[u8],
+    ) -> Result<(), MacError<SPI>> {
+        let radio = self.radio.take().ok_or(MacError::RadioLost)?;
+        let (radio, result) = self.transmit(radio, dst, payload).await?;
+        self.radio = Some(radio);
+
+        result
+    }
+
+    /// Receives the next frame
+    ///
+    /// Frames that fail to decode and stray acknowledgements are dropped.
+    /// Acknowledgements for received frames are sent by the DW3000 itself if
+    /// `AutoAck` is enabled in the radio configuration.
+    #[maybe_async_attr]
+    pub async fn receive<'b>(
+        &mut self,
+        buffer: &'b mut [u8],
+    ) -> Result<Message<'b>, MacError<SPI>> {
+        let mut radio = self.radio.take().ok_or(MacError::RadioLost)?;
+
+        let (len, rx_time, rx_quality) = loop {
+            let mut receiving = radio.receive(self.config.radio).await?;
+            let result = loop {
+                match receiving.r_wait_buf(buffer).await {
+                    Err(nb::Error::WouldBlock) => continue,
+                    Err(nb::Error::Other(Error::Spi(error))) => {
+                        return Err(MacError::Radio(Error::Spi(error)))
+                    }
+                    result => break result,
+                }
+            };
+            radio = receiving
+                .finish_receiving()
+                .await
+                .map_err(|(_, error)| error)?;
+
+            match result {
+                Ok((len, rx_time, rx_quality)) => {
+                    let is_frame =
+                        Ieee802154Frame::new_checked(&buffer[..len]).is_ok_and(|frame| {
+                            frame.frame_type() != Ieee802154FrameType::Acknowledgement
+                        });
+                    if is_frame {
+                        break (len, rx_time, rx_quality);
+                    }
+                }
+                Err(nb::Error::Other(
+                    error @ (Error::FrameWaitTimeout
+                    | Error::PreambleDetectionTimeout
+                    | Error::BufferTooSmall { .. }),
+                )) => {
+                    self.radio = Some(radio);
+                    return Err(MacError::Radio(error));
+                }
+                // Reception errors, just listen again
+                Err(_) => (),
+            }
+        };
+        self.radio = Some(radio);
+
+        // The frame was checked above
+        let frame = Ieee802154Frame::new_unchecked(&buffer[..len]);
+
+        Ok(Message {
+            rx_time,
+            rx_quality,
+            frame,
+        })
+    }
+
+    #[allow(clippy::type_complexity)]
+    #[maybe_async_attr]
+    async fn transmit(
+        &mut self,
+        mut radio: DW3000<SPI, Ready>,
+        dst: Ieee802154Address,
+        payload: &[u8],
+    ) -> Result<(DW3000<SPI, Ready>, Result<(), MacError<SPI>>), Error<SPI>> {
+        let ack_request = !dst.is_broadcast();
+
+        let (pan_id, _) = radio.get_address().await?;
+        let mut frame_header = radio.build_frame_header(Some(dst), Some(pan_id)).await?;
+        frame_header.ack_request = ack_request;
+        let seq = frame_header.sequence_number.unwrap_or_default();
+
+        if let Err(error) = DW3000::<SPI, Ready>::check_frame_len(&frame_header, payload) {
+            return Ok((radio, Err(MacError::Radio(error))));
+        }
+        let mut buffer = [0_u8; 127];
+        let len = DW3000::<SPI, Ready>::emit_frame(&mut buffer, &frame_header, payload);
+
+        let continuation = if ack_request {
+            TxContinuation::Rx
+        } else {
+            TxContinuation::Ready
+        };
+        let config = Config {
+            rx_frame_wait_timeout: Some(self.config.ack_wait_duration),
+            ..self.config.radio
+        };
+
+        for _ in 0..=self.config.max_frame_retries {
+            let mut be = self.config.min_be;
+            let mut backoffs = 0;
+            let (sending, sent) = loop {
+                self.backoff(&mut radio, be).await?;
+
+                let mut sending = radio
+                    .send_raw(
+                        &buffer[0..len + 2],
+                        SendTime::WhenChannelClear(self.config.cca_duration),
+                        continuation,
+                        config,
+                    )
+                    .await?;
+                let result = loop {
+                    match sending.s_wait().await {
+                        Ok(sent) => break Ok(sent),
+                        Err(nb::Error::WouldBlock) => continue,
+                        Err(nb::Error::Other(error)) => break Err(error),
+                    }
+                };
+
+                match result {
+                    Ok(sent) => break (sending, sent),
+                    Err(Error::ChannelBusy) => {
+                        radio = sending.abort_sending().await.map_err(|(_, error)| error)?;
+
+                        backoffs += 1;
+                        be = (be + 1).min(self.config.max_be);
+                        if backoffs > self.config.max_csma_backoffs {
+                            return Ok((radio, Err(MacError::ChannelAccessFailure)));
+                        }
+                    }
+                    Err(error) => return Err(error),
+                }
+            };
+
+            if !ack_request {
+                let radio = sending.finish_sending().await.map_err(|(_, error)| error)?;
+                return Ok((radio, Ok(())));
+            }
+
+            let mut receiving = sending
+                .continue_receiving()
+                .await
+                .map_err(|(_, error)| error)?;
+            let ack = receiving.wait_for_ack(seq, sent).await?;
+            radio = receiving
+                .finish_receiving()
+                .await
+                .map_err(|(_, error)| error)?;
+
+            if ack.is_some() {
+                return Ok((radio, Ok(())));
+            }
+        }
+
+        Ok((radio, Err(MacError::NoAck)))
+    }
+
+    /// Waits for a random number of backoff periods, between 0 and 2^`be` - 1
+    #[maybe_async_attr]
+    async fn backoff(&mut self, radio: &mut DW3000<SPI, Ready>, be: u8) -> Result<(), Error<SPI>> {
+        let start = radio.sys_time().await?;
+
+        // The timer adds entropy from the timing of previous operations
+        self.rng = xorshift(self.rng ^ (start.value() >> 8) as u32);
+        let periods = self.rng % (1 << be.min(8));
+        if periods == 0 {
+            return Ok(());
+        }
+        let delay = Duration::from_nanos(UNIT_BACKOFF_PERIOD_NANOS).value() * periods as u64;
+
+        while radio.sys_time().await?.duration_since(start).value() < delay {}
+
+        Ok(())
+    }
+}
+
+fn xorshift(mut x: u32) -> u32 {
+    // A zero state would never change again
+    if x == 0 {
+        x = 0x9E37_79B9;
+    }
+
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    x
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use embedded_hal_mock::eh1::spi::{Mock as SpiMock, Transaction as SpiTransaction};
+    use smoltcp::wire::{Ieee802154Pan, Ieee802154Repr};
+
+    use crate::{
+        test_utils::{fast_command, read, write},
+        FastCommand,
+    };
+
+    #[maybe_async::test(not(feature = "async"), async(all(feature = "async"), tokio::test))]
+    async fn test_channel_access_failure() {
+        let frame_header = Ieee802154Repr {
+            frame_type: Ieee802154FrameType::Data,
+            frame_version: smoltcp::wire::Ieee802154FrameVersion::Ieee802154_2006,
+            security_enabled: false,
+            sequence_number: Some(0),
+            frame_pending: false,
+            ack_request: false,
+            pan_id_compression: true,
+            dst_addr: Some(Ieee802154Address::BROADCAST),
+            src_addr: Some(Ieee802154Address::Short([0x04, 0x03])),
+            src_pan_id: Some(Ieee802154Pan(0x0605)),
+            dst_pan_id: Some(Ieee802154Pan(0x0605)),
+        };
+        let mut tx_buffer = [0_u8; 127];
+        let len = DW3000::<SpiMock<u8>, Ready>::emit_frame(&mut tx_buffer, &frame_header, b"hi");
+        // Frame, footer and CRC
+        let txflen = (len + 4) as u16 | (0b0101 << 12);
+
+        let expectations = [
+            // Frame header
+            read(0x00, 0x0C, &PANADR),
+            read(0x00, 0x0C, &PANADR),
+            // Backoff
+            read(0x00, 0x1C, &[0, 0, 0, 0]),
+            // Event counters
+            read(0x0F, 0x00, &[0]),
+            write(0x0F, 0x00, &[0b10]),
+            read(0x0F, 0x00, &[0]),
+            write(0x0F, 0x00, &[0b01]),
+            // Frame
+            write(0x14, 0x00, &tx_buffer),
+            read(0x00, 0x24, &[0; 6]),
+            write(0x00, 0x24, &[txflen as u8, (txflen >> 8) as u8, 0, 0, 0, 0]),
+            // CCA
+            write(0x06, 0x04, &[8, 0]),
+            fast_command(FastCommand::CMD_CCA_TX),
+            // Preamble detected
+            read(0x0F, 0x18, &[0]),
+            read(0x00, 0x44, &[0, 0, 0, 0, 0, 0x10]),
+            write(0x06, 0x04, &[0, 0]),
+            write(0x00, 0x44, &[0, 0, 0, 0, 0, 0x10]),
+            // Back to idle
+            fast_command(FastCommand::CMD_TXRXOFF),
+            write(0x00, 0x44, &[0xF0, 0, 0, 0, 0, 0]),
+        ]
+        .concat();
+
+        let spi = SpiMock::new(&expectations);
+
+        let mut mac = Mac::new(
+            DW3000::new_ready(spi),
+            MacConfig {
+                min_be: 0,
+                max_csma_backoffs: 0,
+                ..Default::default()
+            },
+        );
+
+        let result = mac.send(Ieee802154Address::BROADCAST, b"hi").await;
+        assert!(matches!(result, Err(MacError::ChannelAccessFailure)));
+
+        mac.release().unwrap().ll().bus().done();
+    }
+
+    /// PAN 0x0605, short address 0x0403
+    const PANADR: [u8; 4] = [0x03, 0x04, 0x05, 0x06];
+
+    /// The destination of acknowledged frames
+    const DST: Ieee802154Address = Ieee802154Address::Short([0x02, 0x01]);
+
+    /// The TX buffer and TX_FCTRL of a data frame to `DST` with sequence
+    /// number 0
+    fn ack_request_frame(payload: &[u8]) -> (Vec<u8>, [u8; 6]) {
+        let frame_header = Ieee802154Repr {
+            frame_type: Ieee802154FrameType::Data,
+            frame_version: smoltcp::wire::Ieee802154FrameVersion::Ieee802154_2006,
+            security_enabled: false,
+            sequence_number: Some(0),
+            frame_pending: false,
+            ack_request: true,
+            pan_id_compression: true,
+            dst_addr: Some(DST),
+            src_addr: Some(Ieee802154Address::Short([0x04, 0x03])),
+            src_pan_id: Some(Ieee802154Pan(0x0605)),
+            dst_pan_id: Some(Ieee802154Pan(0x0605)),
+        };
+        let mut tx_buffer = [0_u8; 127];
+        let len = DW3000::<SpiMock<u8>, Ready>::emit_frame(&mut tx_buffer, &frame_header, payload);
+        // Frame, footer and CRC
+        let txflen = (len + 4) as u16 | (0b0101 << 12);
+
+        (
+            tx_buffer.to_vec(),
+            [txflen as u8, (txflen >> 8) as u8, 0, 0, 0, 0],
+        )
+    }
+
+    /// One transmission of a frame that requests an acknowledgement, up to
+    /// the start of the ACK reception
+    fn send_ack_request(tx_buffer: &[u8], tx_fctrl: &[u8]) -> Vec<SpiTransaction<u8>> {
+        [
+            // Backoff
+            read(0x00, 0x1C, &[0, 0, 0, 0]),
+            // Event counters
+            read(0x0F, 0x00, &[0]),
+            write(0x0F, 0x00, &[0b10]),
+            read(0x0F, 0x00, &[0]),
+            write(0x0F, 0x00, &[0b01]),
+            // Frame
+            write(0x14, 0x00, tx_buffer),
+            read(0x00, 0x24, &[0; 6]),
+            write(0x00, 0x24, tx_fctrl),
+            // ACK wait timeout of 1 ms
+            write(0x00, 0x34, &[0xCF, 0x03, 0x00]),
+            read(0x00, 0x10, &[0; 4]),
+            write(0x00, 0x10, &[0, 0x02, 0, 0]),
+            write(0x06, 0x04, &[0, 0]),
+            write(0x11, 0x1A, &[0, 0, 0, 0]),
+            // CCA
+            write(0x06, 0x04, &[8, 0]),
+            fast_command(FastCommand::CMD_CCA_TX_W4R),
+            // Frame sent
+            read(0x0F, 0x18, &[0]),
+            read(0x00, 0x44, &[0x80, 0, 0, 0, 0, 0]),
+            write(0x06, 0x04, &[0, 0]),
+            write(0x00, 0x44, &[0xF0, 0, 0, 0, 0, 0]),
+            read(0x00, 0x74, &[0; 5]),
+            // Continue receiving
+            write(0x00, 0x44, &[0xF0, 0, 0, 0, 0, 0]),
+        ]
+        .concat()
+    }
+
+    /// The ACK wait timeout expires
+    fn ack_timeout() -> Vec<SpiTransaction<u8>> {
+        [
+            read(0x00, 0x44, &[0, 0, 0x02, 0, 0, 0]),
+            write(0x00, 0x44, &[0, 0xFF, 0x37, 0x24, 0x02, 0]),
+        ]
+        .concat()
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(all(feature = "async"), tokio::test))]
+    async fn test_no_ack() {
+        // 123 bytes of header and payload, the longest frame that fits
+        let payload = [0; 114];
+        let (tx_buffer, tx_fctrl) = ack_request_frame(&payload);
+        assert_eq!(tx_fctrl[0], 127);
+
+        let expectations = [
+            // Frame header
+            read(0x00, 0x0C, &PANADR),
+            read(0x00, 0x0C, &PANADR),
+            // First transmission and retry
+            send_ack_request(&tx_buffer, &tx_fctrl),
+            ack_timeout(),
+            send_ack_request(&tx_buffer, &tx_fctrl),
+            ack_timeout(),
+        ]
+        .concat();
+
+        let spi = SpiMock::new(&expectations);
+
+        let mut mac = Mac::new(
+            DW3000::new_ready(spi),
+            MacConfig {
+                min_be: 0,
+                max_frame_retries: 1,
+                ..Default::default()
+            },
+        );
+
+        let result = mac.send(DST, &payload).await;
+        assert!(matches!(result, Err(MacError::NoAck)));
+
+        mac.release().unwrap().ll().bus().done();
+    }
+
+    /// An acknowledgement for sequence number `seq` arrives
+    fn ack_received(seq: u8) -> Vec<SpiTransaction<u8>> {
+        let mut rx_buffer = [0_u8; 127];
+        rx_buffer[..5].copy_from_slice(&[0x02, 0x00, seq, 0x00, 0x00]);
+
+        [
+            read(0x00, 0x44, &[0, 0x40, 0, 0, 0, 0]),
+            read(0x00, 0x64, &[0; 5]),
+            #[cfg(feature = "rssi")]
+            [
+                read(0x00, 0x10, &[0x80, 0, 0, 0]),
+                read(0x0C, 0x30, &[0, 0, 1, 0]),
+                read(0x0C, 0x34, &[0; 4]),
+                read(0x0C, 0x38, &[0; 4]),
+                read(0x0C, 0x58, &[1, 0, 0, 0]),
+                read(0x03, 0x18, &[0; 2]),
+            ]
+            .concat(),
+            #[cfg(not(feature = "rssi"))]
+            vec![],
+            write(0x00, 0x44, &[0, 0xFF, 0x37, 0x24, 0x02, 0]),
+            read(0x00, 0x4C, &[5, 0, 0, 0]),
+            read(0x12, 0x00, &rx_buffer),
+        ]
+        .concat()
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(all(feature = "async"), tokio::test))]
+    async fn test_ack() {
+        let (tx_buffer, tx_fctrl) = ack_request_frame(b"hi");
+
+        let expectations = [
+            // Frame header
+            read(0x00, 0x0C, &PANADR),
+            read(0x00, 0x0C, &PANADR),
+            send_ack_request(&tx_buffer, &tx_fctrl),
+            ack_received(0),
+        ]
+        .concat();
+
+        let spi = SpiMock::new(&expectations);
+
+        let mut mac = Mac::new(
+            DW3000::new_ready(spi),
+            MacConfig {
+                min_be: 0,
+                ..Default::default()
+            },
+        );
+
+        let result = mac.send(DST, b"hi").await;
+        assert!(result.is_ok());
+
+        mac.release().unwrap().ll().bus().done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(all(feature = "async"), tokio::test))]
+    async fn test_retransmission() {
+        let (tx_buffer, tx_fctrl) = ack_request_frame(b"hi");
+
+        let expectations = [
+            // Frame header
+            read(0x00, 0x0C, &PANADR),
+            read(0x00, 0x0C, &PANADR),
+            // The first transmission isn't acknowledged
+            send_ack_request(&tx_buffer, &tx_fctrl),
+            ack_timeout(),
+            // The same frame is sent again
+            send_ack_request(&tx_buffer, &tx_fctrl),
+            ack_received(0),
+        ]
+        .concat();
+
+        let spi = SpiMock::new(&expectations);
+
+        let mut mac = Mac::new(
+            DW3000::new_ready(spi),
+            MacConfig {
+                min_be: 0,
+                ..Default::default()
+            },
+        );
+
+        let result = mac.send(DST, b"hi").await;
+        assert!(result.is_ok());
+
+        mac.release().unwrap().ll().bus().done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(all(feature = "async"), tokio::test))]
+    async fn test_ack_after_other_frame() {
+        let (tx_buffer, tx_fctrl) = ack_request_frame(b"hi");
+
+        let expectations = [
+            // Frame header
+            read(0x00, 0x0C, &PANADR),
+            read(0x00, 0x0C, &PANADR),
+            send_ack_request(&tx_buffer, &tx_fctrl),
+            // An acknowledgement for another frame
+            ack_received(5),
+            // Listen for the remaining 847 units of the ACK wait timeout
+            read(0x00, 0x1C, &[0x00, 0x80, 0x00, 0x00]),
+            write(0x00, 0x34, &[0x4F, 0x03, 0x00]),
+            read(0x00, 0x10, &[0; 4]),
+            write(0x00, 0x10, &[0, 0x02, 0, 0]),
+            write(0x06, 0x04, &[0, 0]),
+            fast_command(FastCommand::CMD_RX),
+            ack_received(0),
+        ]
+        .concat();
+
+        let spi = SpiMock::new(&expectations);
+
+        let mut mac = Mac::new(
+            DW3000::new_ready(spi),
+            MacConfig {
+                min_be: 0,
+                max_frame_retries: 0,
+                ..Default::default()
+            },
+        );
+
+        let result = mac.send(DST, b"hi").await;
+        assert!(result.is_ok());
+
+        mac.release().unwrap().ll().bus().done();
+    }
+
+    #[maybe_async::test(not(feature = "async"), async(all(feature = "async"), tokio::test))]
+    async fn test_frame_too_long() {
+        let expectations = [
+            // Frame header
+            read(0x00, 0x0C, &PANADR),
+            read(0x00, 0x0C, &PANADR),
+        ]
+        .concat();
+
+        let spi = SpiMock::new(&expectations);
+
+        let mut mac = Mac::new(DW3000::new_ready(spi), MacConfig::default());
+
+        // 124 bytes of header and payload
+        let result = mac.send(DST, &[0; 115]).await;
+        assert!(matches!(result, Err(MacError::Radio(Error::FrameTooLong))));
+
+        mac.release().unwrap().ll().bus().done();
+    }
+}
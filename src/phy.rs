@@ -0,0 +1,305 @@
+//! [`smoltcp::phy::Device`] implementation
+//!
+//! [`Phy`] lets a smoltcp `Interface` with the IEEE 802.15.4 medium, and thus
+//! 6LoWPAN, run directly on the DW3000. The driver is moved between its
+//! `Ready`, `Sending` and receiving states internally.
+//!
+//! smoltcp's device API is blocking, so this module is only available without
+//! the `async` feature, which is enabled by default. Disable the default
+//! features to use it:
+//!
+//! ```toml
+//! [dependencies]
+//! dw3000-ng = { version = "1.0", default-features = false, features = ["rssi"] }
+//! ```
+
+use core::mem;
+
+use smoltcp::{
+    phy::{self, DeviceCapabilities, Medium},
+    time::Instant,
+};
+
+use crate::{
+    configs::TxContinuation, hl::SendTime, Config, Error, Ready, SingleBufferReceiving, DW3000,
+};
+
+/// The largest frame the DW3000 sends and receives, including the FCS
+pub(crate) const MAX_FRAME_LEN: usize = 127;
+
+/// The length of the FCS, which the DW3000 appends and checks by itself
+const FCS_LEN: usize = 2;
+
+enum State<SPI> {
+    Ready(DW3000<SPI, Ready>),
+    Receiving(DW3000<SPI, SingleBufferReceiving>),
+    /// An SPI error happened during a state transition
+    Lost,
+}
+
+impl<SPI> State<SPI>
+where
+    SPI: embedded_hal::spi::SpiDevice<u8>,
+{
+    /// Stops receiving, if the receiver is on
+    fn stop_receiving(&mut self) -> Result<(), Error<SPI>> {
+        let (state, result) = match mem::replace(self, State::Lost) {
+            State::Receiving(radio) => match radio.finish_receiving() {
+                Ok(radio) => (State::Ready(radio), Ok(())),
+                Err((_, error)) => (State::Lost, Err(error)),
+            },
+            state => (state, Ok(())),
+        };
+        *self = state;
+
+        result
+    }
+
+    /// Turns the receiver on, if the radio is idle
+    fn start_receiving(&mut self, config: Config) -> Result<(), Error<SPI>> {
+        let (state, result) = match mem::replace(self, State::Lost) {
+            State::Ready(radio) => match radio.receive(config) {
+                Ok(radio) => (State::Receiving(radio), Ok(())),
+                Err(error) => (State::Lost, Err(error)),
+            },
+            state => (state, Ok(())),
+        };
+        *self = state;
+
+        result
+    }
+
+    /// Sends `frame` and waits until it is sent
+    ///
+    /// Does nothing if the radio was lost before.
+    fn send(&mut self, frame: &[u8], config: Config) -> Result<(), Error<SPI>> {
+        self.stop_receiving()?;
+
+        let (state, result) = match mem::replace(self, State::Lost) {
+            State::Ready(radio) => {
+                match radio.send_raw(frame, SendTime::Now, TxContinuation::Ready, config) {
+                    Ok(mut radio) => {
+                        let result = nb::block!(radio.s_wait()).map(|_| ());
+                        match radio.finish_sending() {
+                            Ok(radio) => (State::Ready(radio), result),
+                            Err((_, error)) => (State::Lost, Err(error)),
+                        }
+                    }
+                    Err(error) => (State::Lost, Err(error)),
+                }
+            }
+            state => (state, Ok(())),
+        };
+        *self = state;
+
+        result
+    }
+}
+
+/// smoltcp device for the IEEE 802.15.4 medium
+///
+/// The receiver is kept on while smoltcp polls for frames and is turned off
+/// for each transmission. Frames handed to smoltcp don't include the FCS.
+///
+/// If the radio reports an error while changing states, it is lost and the
+/// device stops sending and receiving. smoltcp can't be told that a
+/// transmission failed, so the last error is kept until it is taken with
+/// [`Phy::take_tx_error`].
+pub struct Phy<SPI>
+where
+    SPI: embedded_hal::spi::SpiDevice<u8>,
+{
+    state: State<SPI>,
+    config: Config,
+    rx_buffer: [u8; MAX_FRAME_LEN],
+    tx_error: Option<Error<SPI>>,
+}
+
+impl<SPI> Phy<SPI>
+where
+    SPI: embedded_hal::spi::SpiDevice<u8>,
+{
+    /// Creates a device from an initialized radio
+    ///
+    /// `config` is used for both sending and receiving.
+    pub fn new(radio: DW3000<SPI, Ready>, config: Config) -> Self {
+        Phy {
+            state: State::Ready(radio),
+            config,
+            rx_buffer: [0; MAX_FRAME_LEN],
+            tx_error: None,
+        }
+    }
+
+    /// Returns the error of the last failed transmission, if there was one
+    /// since the last call
+    pub fn take_tx_error(&mut self) -> Option<Error<SPI>> {
+        self.tx_error.take()
+    }
+
+    /// Returns true if the radio was lost to an error
+    pub fn is_lost(&self) -> bool {
+        matches!(self.state, State::Lost)
+    }
+
+    /// Returns true if the receiver is on
+    pub fn is_receiving(&self) -> bool {
+        matches!(self.state, State::Receiving(_))
+    }
+
+    /// Returns the radio, unless it was lost to an error
+    pub fn release(mut self) -> Option<DW3000<SPI, Ready>> {
+        self.state.stop_receiving().ok()?;
+
+        match self.state {
+            State::Ready(radio) => Some(radio),
+            _ => None,
+        }
+    }
+}
+
+impl<SPI> phy::Device for Phy<SPI>
+where
+    SPI: embedded_hal::spi::SpiDevice<u8>,
+{
+    type RxToken<'a>
+        = RxToken<'a>
+    where
+        Self: 'a;
+    type TxToken<'a>
+        = TxToken<'a, SPI>
+    where
+        Self: 'a;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        // An error loses the radio, which `is_lost` reports
+        self.state.start_receiving(self.config).ok()?;
+
+        let State::Receiving(radio) = &mut self.state else {
+            return None;
+        };
+        let result = match radio.r_wait_buf(&mut self.rx_buffer) {
+            Err(nb::Error::WouldBlock) => return None,
+            result => result,
+        };
+
+        // The receiver stops after each frame. It is turned on again on the
+        // next poll.
+        self.state.stop_receiving().ok()?;
+
+        let (len, _, _) = result.ok()?;
+        if len < FCS_LEN {
+            return None;
+        }
+
+        Some((
+            RxToken {
+                frame: &self.rx_buffer[..len - FCS_LEN],
+            },
+            TxToken {
+                state: &mut self.state,
+                config: self.config,
+                error: &mut self.tx_error,
+            },
+        ))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        if self.is_lost() {
+            return None;
+        }
+
+        Some(TxToken {
+            state: &mut self.state,
+            config: self.config,
+            error: &mut self.tx_error,
+        })
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut capabilities = DeviceCapabilities::default();
+        capabilities.medium = Medium::Ieee802154;
+        capabilities.max_transmission_unit = MAX_FRAME_LEN - FCS_LEN;
+
+        capabilities
+    }
+}
+
+/// Receive token of [`Phy`]
+pub struct RxToken<'a> {
+    frame: &'a [u8],
+}
+
+impl phy::RxToken for RxToken<'_> {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        f(self.frame)
+    }
+}
+
+/// Transmit token of [`Phy`]
+pub struct TxToken<'a, SPI>
+where
+    SPI: embedded_hal::spi::SpiDevice<u8>,
+{
+    state: &'a mut State<SPI>,
+    config: Config,
+    error: &'a mut Option<Error<SPI>>,
+}
+
+impl<SPI> phy::TxToken for TxToken<'_, SPI>
+where
+    SPI: embedded_hal::spi::SpiDevice<u8>,
+{
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        let mut buffer = [0; MAX_FRAME_LEN - FCS_LEN];
+
+        // smoltcp doesn't ask for more than the MTU. If it does anyway, the
+        // frame is cut short and dropped.
+        if len > buffer.len() {
+            let result = f(&mut buffer);
+            *self.error = Some(Error::FrameTooLong);
+            return result;
+        }
+
+        let result = f(&mut buffer[..len]);
+
+        if let Err(error) = self.state.send(&buffer[..len], self.config) {
+            #[cfg(feature = "defmt")]
+            defmt::warn!("Failed to send frame");
+
+            *self.error = Some(error);
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use embedded_hal_mock::eh1::spi::Mock as SpiMock;
+    use smoltcp::phy::{Device, TxToken as _};
+
+    #[test]
+    fn oversized_frame() {
+        let spi = SpiMock::new(&[]);
+        let mut phy = Phy::new(DW3000::new_ready(spi), Config::default());
+
+        let tx = phy.transmit(Instant::ZERO).unwrap();
+        let len = tx.consume(MAX_FRAME_LEN, |buffer| buffer.len());
+
+        // The frame is cut to the MTU and not sent
+        assert_eq!(len, MAX_FRAME_LEN - FCS_LEN);
+        assert!(matches!(phy.take_tx_error(), Some(Error::FrameTooLong)));
+        assert!(phy.take_tx_error().is_none());
+
+        phy.release().unwrap().ll().bus().done();
+    }
+}
@@ -0,0 +1,359 @@
+//! TDMA superframe scheduling
+//!
+//! A superframe starts with a beacon from the coordinator at the beginning of
+//! slot 0, followed by equally long slots. [`Tdma`] keeps track of the last
+//! beacon in local DW3000 system time and computes when each slot starts, so
+//! frames can be sent and received with [`SendTime::Delayed`] and
+//! [`ReceiveTime::Delayed`].
+//!
+//! Slot times are defined in the coordinator's clock. Nodes correct them with
+//! the clock offset measured on the beacon (see
+//! [`DW3000::get_clock_offset`](crate::DW3000::get_clock_offset)), so their
+//! slots don't drift away from the coordinator's within a superframe.
+
+use smoltcp::wire::Ieee802154Address;
+
+use crate::{
+    hl::{ReceiveTime, SendTime},
+    time::{Duration, Instant, TIME_MAX},
+    Config,
+};
+
+/// An error that can occur in the [`Tdma`] scheduler
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TdmaError {
+    /// The slot number is not part of the superframe
+    InvalidSlot,
+    /// No beacon has been received or sent yet
+    NotSynchronized,
+    /// The slot duration is 0, or the superframe takes up more than a quarter
+    /// of the range of the DW3000 timer
+    InvalidSlotDuration,
+}
+
+/// The default drift margin of [`Tdma`] in ns
+///
+/// This covers the clock drift that remains after the clock offset
+/// correction, and the jitter of the delayed send and receive times.
+const DEFAULT_DRIFT_MARGIN_NANOS: u32 = 10_000;
+
+/// TDMA scheduler for superframes of `SLOTS` slots
+#[derive(Copy, Clone, Debug)]
+pub struct Tdma<const SLOTS: usize> {
+    slot_duration: Duration,
+    sync_duration: Duration,
+    drift_margin: Duration,
+    beacon: Option<Instant>,
+    clock_offset: i16,
+    assignments: [Option<Ieee802154Address>; SLOTS],
+}
+
+impl<const SLOTS: usize> Tdma<SLOTS> {
+    /// Creates a scheduler with slots of `slot_duration`
+    ///
+    /// The guard time is the time the preamble, SFD and PHY header take at
+    /// the bitrate configured in `config`, plus a drift margin of 10 µs (see
+    /// [`Tdma::with_drift_margin`]). Receivers open their window that much
+    /// before the slot starts, and each slot should leave that much time
+    /// after the longest frame (see [`Config::frame_duration`]).
+    ///
+    /// Returns [`TdmaError::InvalidSlotDuration`] if `slot_duration` is 0, or
+    /// if a superframe is longer than about 4.3 s, a quarter of the range of
+    /// the DW3000 timer. That way the next slot is less than half the range
+    /// away, which [`Tdma::next_slot`] relies on to tell it from a past one.
+    pub fn new(slot_duration: Duration, config: &Config) -> Result<Self, TdmaError> {
+        let superframe_duration = slot_duration.value().checked_mul(SLOTS as u64);
+        if slot_duration.value() == 0
+            || !matches!(superframe_duration, Some(d) if d <= TIME_MAX / 4)
+        {
+            return Err(TdmaError::InvalidSlotDuration);
+        }
+
+        Ok(Tdma {
+            slot_duration,
+            sync_duration: config.frame_duration(0),
+            drift_margin: Duration::from_nanos(DEFAULT_DRIFT_MARGIN_NANOS),
+            beacon: None,
+            clock_offset: 0,
+            assignments: [None; SLOTS],
+        })
+    }
+
+    /// Replaces the drift margin that is added to the guard time
+    ///
+    /// Use a larger margin for long superframes, or when beacons are missed
+    /// and the clock offset correction gets stale.
+    pub fn with_drift_margin(mut self, drift_margin: Duration) -> Self {
+        self.drift_margin = drift_margin;
+        self
+    }
+
+    /// Returns the duration of a slot
+    pub fn slot_duration(&self) -> Duration {
+        self.slot_duration
+    }
+
+    /// Returns the guard time before each slot
+    pub fn guard_time(&self) -> Duration {
+        // Both parts are far shorter than the 17 s wrap-around of the timer
+        Duration::new(self.sync_duration.value() + self.drift_margin.value()).unwrap()
+    }
+
+    /// Returns the duration of the receive window of a slot
+    ///
+    /// Use this as `Config::rx_frame_wait_timeout` together with
+    /// [`Tdma::receive_time`], so the receiver turns off at the end of the
+    /// slot.
+    pub fn rx_window(&self) -> Duration {
+        // Slots are far shorter than the 17 s wrap-around of the timer
+        Duration::new(self.guard_time().value() + self.slot_duration.value()).unwrap()
+    }
+
+    /// Assigns `slot` to `owner`, or frees it when `owner` is `None`
+    pub fn assign_slot(
+        &mut self,
+        slot: usize,
+        owner: Option<Ieee802154Address>,
+    ) -> Result<(), TdmaError> {
+        *self
+            .assignments
+            .get_mut(slot)
+            .ok_or(TdmaError::InvalidSlot)? = owner;
+
+        Ok(())
+    }
+
+    /// Returns the owner of `slot`
+    pub fn slot_owner(&self, slot: usize) -> Option<Ieee802154Address> {
+        self.assignments.get(slot).copied().flatten()
+    }
+
+    /// Returns the first slot assigned to `owner`
+    pub fn slot_of(&self, owner: Ieee802154Address) -> Option<usize> {
+        self.assignments
+            .iter()
+            .position(|slot| *slot == Some(owner))
+    }
+
+    /// Synchronizes to a beacon sent by the coordinator
+    ///
+    /// `rx_time` is the RX timestamp of the beacon and `clock_offset` the
+    /// clock offset measured on it, as returned by
+    /// [`DW3000::get_clock_offset`](crate::DW3000::get_clock_offset).
+    pub fn sync_to_beacon(&mut self, rx_time: Instant, clock_offset: i16) {
+        self.beacon = Some(rx_time);
+        self.clock_offset = clock_offset;
+    }
+
+    /// Sets the TX timestamp of the beacon sent by this device, if it is the
+    /// coordinator
+    pub fn set_beacon_time(&mut self, tx_time: Instant) {
+        self.beacon = Some(tx_time);
+        self.clock_offset = 0;
+    }
+
+    /// Returns the local time at which `slot` of the `superframe`-th
+    /// superframe after the last beacon starts
+    pub fn slot_start(&self, superframe: u32, slot: usize) -> Result<Instant, TdmaError> {
+        if slot >= SLOTS {
+            return Err(TdmaError::InvalidSlot);
+        }
+        let beacon = self.beacon.ok_or(TdmaError::NotSynchronized)?;
+
+        let slots = superframe as u64 * SLOTS as u64 + slot as u64;
+        let offset = self.slot_duration.value().wrapping_mul(slots) & TIME_MAX;
+
+        // Convert from the coordinator's clock to ours
+        let correction = (offset as i64 * self.clock_offset as i64) >> 26;
+        let offset = (offset as i64 - correction) as u64 & TIME_MAX;
+
+        // `offset` was masked to 40 bits, so this never panics
+        Ok(beacon + Duration::new(offset).unwrap())
+    }
+
+    /// Returns the first superframe after `now` in which `slot` starts
+    /// later than `now`, together with the start of the slot
+    pub fn next_slot(&self, now: Instant, slot: usize) -> Result<(u32, Instant), TdmaError> {
+        let beacon = self.beacon.ok_or(TdmaError::NotSynchronized)?;
+
+        // `new` made sure this is neither 0 nor overflows
+        let superframe_duration = self.slot_duration.value() * SLOTS as u64;
+        let first = now.duration_since(beacon).value() / superframe_duration;
+        let first = u32::try_from(first).map_err(|_| TdmaError::InvalidSlotDuration)?;
+
+        // The slot starts after `now` in the first or second superframe after
+        // the current one, the third one leaves room for the clock offset
+        // correction
+        for superframe in (first..).take(3) {
+            let start = self.slot_start(superframe, slot)?;
+            if start.duration_since(now).value() != 0
+                && start.duration_since(now).value() < TIME_MAX / 2
+            {
+                return Ok((superframe, start));
+            }
+        }
+
+        Err(TdmaError::InvalidSlotDuration)
+    }
+
+    /// Returns the send time for a frame in `slot` of the `superframe`-th
+    /// superframe after the last beacon
+    pub fn send_time(&self, superframe: u32, slot: usize) -> Result<SendTime, TdmaError> {
+        let start = self.slot_start(superframe, slot)?;
+
+        Ok(SendTime::Delayed(round_up(start)))
+    }
+
+    /// Returns the receive time for a frame in `slot` of the `superframe`-th
+    /// superframe after the last beacon
+    ///
+    /// The receiver is turned on the guard time before the slot starts.
+    pub fn receive_time(&self, superframe: u32, slot: usize) -> Result<ReceiveTime, TdmaError> {
+        let start = self.slot_start(superframe, slot)?;
+
+        Ok(ReceiveTime::Delayed(round_down(start - self.guard_time())))
+    }
+}
+
+/// Rounds up to the resolution of delayed transmissions
+fn round_up(time: Instant) -> Instant {
+    // Masked to 40 bits, so this never panics
+    Instant::new((time.value() + 0x1FF) & !0x1FF & TIME_MAX).unwrap()
+}
+
+/// Rounds down to the resolution of delayed receptions
+fn round_down(time: Instant) -> Instant {
+    // Rounding down never leaves the 40-bit range
+    Instant::new(time.value() & !0x1FF).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::configs::{BitRate, PhrRate};
+
+    fn tdma() -> Tdma<4> {
+        Tdma::new(Duration::new(1 << 20).unwrap(), &Config::default()).unwrap()
+    }
+
+    #[test]
+    fn invalid_slot_duration() {
+        let result: Result<Tdma<4>, _> = Tdma::new(Duration::new(0).unwrap(), &Config::default());
+        assert_eq!(result.unwrap_err(), TdmaError::InvalidSlotDuration);
+
+        // A superframe of more than a quarter of the timer range
+        let slot_duration = Duration::new(TIME_MAX / 16 + 1).unwrap();
+        let result: Result<Tdma<4>, _> = Tdma::new(slot_duration, &Config::default());
+        assert_eq!(result.unwrap_err(), TdmaError::InvalidSlotDuration);
+    }
+
+    #[test]
+    fn slot_assignment() {
+        let mut tdma = tdma();
+        let node = Ieee802154Address::Short([0x12, 0x34]);
+
+        assert_eq!(tdma.assign_slot(2, Some(node)), Ok(()));
+        assert_eq!(tdma.assign_slot(4, Some(node)), Err(TdmaError::InvalidSlot));
+        assert_eq!(tdma.slot_owner(2), Some(node));
+        assert_eq!(tdma.slot_of(node), Some(2));
+
+        assert_eq!(tdma.assign_slot(2, None), Ok(()));
+        assert_eq!(tdma.slot_of(node), None);
+    }
+
+    #[test]
+    fn slot_start() {
+        let mut tdma = tdma();
+        assert_eq!(
+            tdma.slot_start(0, 1).unwrap_err(),
+            TdmaError::NotSynchronized
+        );
+
+        let beacon = Instant::new(TIME_MAX - (1 << 20) + 1).unwrap();
+        tdma.set_beacon_time(beacon);
+        assert_eq!(tdma.slot_start(0, 0).unwrap().value(), beacon.value());
+        // Wraps around
+        assert_eq!(tdma.slot_start(0, 1).unwrap().value(), 0);
+        assert_eq!(tdma.slot_start(1, 1).unwrap().value(), 4 << 20);
+        assert_eq!(tdma.slot_start(0, 4).unwrap_err(), TdmaError::InvalidSlot);
+
+        // The local clock is slower by 2^-15, so the slot starts earlier
+        tdma.sync_to_beacon(Instant::new(0).unwrap(), 1 << 11);
+        assert_eq!(tdma.slot_start(0, 2).unwrap().value(), (2 << 20) - 64);
+    }
+
+    #[test]
+    fn next_slot() {
+        let mut tdma = tdma();
+        tdma.set_beacon_time(Instant::new(0).unwrap());
+
+        let (superframe, start) = tdma
+            .next_slot(Instant::new((5 << 20) - 1).unwrap(), 1)
+            .unwrap();
+        assert_eq!(superframe, 1);
+        assert_eq!(start.value(), 5 << 20);
+
+        let (superframe, start) = tdma
+            .next_slot(Instant::new((5 << 20) + 1).unwrap(), 1)
+            .unwrap();
+        assert_eq!(superframe, 2);
+        assert_eq!(start.value(), 9 << 20);
+    }
+
+    #[test]
+    fn delayed_times() {
+        // Slots longer than the guard time
+        let mut tdma: Tdma<4> =
+            Tdma::new(Duration::new(1 << 24).unwrap(), &Config::default()).unwrap();
+        tdma.set_beacon_time(Instant::new(0x3FF).unwrap());
+
+        let SendTime::Delayed(time) = tdma.send_time(0, 0).unwrap() else {
+            panic!("not a delayed send time");
+        };
+        assert_eq!(time.value(), 0x400);
+
+        let ReceiveTime::Delayed(time) = tdma.receive_time(0, 1).unwrap() else {
+            panic!("not a delayed receive time");
+        };
+        let expected = 0x3FF + (1 << 24) - tdma.guard_time().value();
+        assert_eq!(time.value(), expected & !0x1FF);
+    }
+
+    #[test]
+    fn guard_time() {
+        let config = Config {
+            bitrate: BitRate::Kbps6800,
+            phr_rate: PhrRate::DataRate,
+            ..Config::default()
+        };
+        let tdma: Tdma<4> = Tdma::new(Duration::new(1 << 24).unwrap(), &config).unwrap();
+
+        // Preamble and SFD, the 21 bits of the PHY header at 6.8 Mbps, plus
+        // the default margin
+        let sync = config.preamble_duration().value() + 197_331;
+        assert_eq!(
+            tdma.guard_time().value(),
+            sync + Duration::from_nanos(10_000).value()
+        );
+
+        // The PHY header takes longer at a lower bitrate
+        let slow = Config {
+            bitrate: BitRate::Kbps850,
+            ..config
+        };
+        let slow_tdma: Tdma<4> = Tdma::new(Duration::new(1 << 24).unwrap(), &slow).unwrap();
+        assert!(slow_tdma.guard_time().value() > tdma.guard_time().value());
+
+        let tdma = tdma.with_drift_margin(Duration::from_nanos(50_000));
+        assert_eq!(
+            tdma.guard_time().value(),
+            sync + Duration::from_nanos(50_000).value()
+        );
+        assert_eq!(
+            tdma.rx_window().value(),
+            tdma.guard_time().value() + (1 << 24)
+        );
+    }
+}
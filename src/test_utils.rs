@@ -0,0 +1,48 @@
+//! Helpers to build the SPI transactions expected by the mocked DW3000
+
+use embedded_hal_mock::eh1::spi::Transaction as SpiTransaction;
+
+use crate::FastCommand;
+
+/// The two header bytes of a full register access
+pub fn header(write: bool, id: u8, sub_id: u8) -> Vec<u8> {
+    vec![
+        ((write as u8) << 7) | (1 << 6) | ((id << 1) & 0x3e) | ((sub_id >> 6) & 0x01),
+        sub_id << 2,
+    ]
+}
+
+/// A register read that returns `response`
+pub fn read(id: u8, sub_id: u8, response: &[u8]) -> Vec<SpiTransaction<u8>> {
+    let mut expected = header(false, id, sub_id);
+    expected.resize(2 + response.len(), 0);
+    let mut reply = header(false, id, sub_id);
+    reply.extend_from_slice(response);
+
+    vec![
+        SpiTransaction::transaction_start(),
+        SpiTransaction::transfer_in_place(expected, reply),
+        SpiTransaction::transaction_end(),
+    ]
+}
+
+/// A register write of `data`
+pub fn write(id: u8, sub_id: u8, data: &[u8]) -> Vec<SpiTransaction<u8>> {
+    let mut expected = header(true, id, sub_id);
+    expected.extend_from_slice(data);
+
+    vec![
+        SpiTransaction::transaction_start(),
+        SpiTransaction::write_vec(expected),
+        SpiTransaction::transaction_end(),
+    ]
+}
+
+/// A fast command
+pub fn fast_command(command: FastCommand) -> Vec<SpiTransaction<u8>> {
+    vec![
+        SpiTransaction::transaction_start(),
+        SpiTransaction::write_vec(vec![0x81 | (((command as u8) << 1) & 0x3e)]),
+        SpiTransaction::transaction_end(),
+    ]
+}
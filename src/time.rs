@@ -98,6 +98,17 @@ impl Instant {
             Duration(TIME_MAX - earlier.value() + self.value() + 1)
         }
     }
+
+    /// Returns the value to write into the DX_TIME or DREF_TIME register
+    ///
+    /// Those registers hold the upper 32 bits of the 40-bit timestamp, and the
+    /// DW3000 ignores their lowest bit. The lowest 9 bits of the time are
+    /// truncated, so the DW3000 acts up to 512 time units (about 8 ns) early.
+    pub(crate) fn dx_time(&self) -> u32 {
+        // NOTE: DW3000's DX_TIME register is 32 bits wide, but only the top 31 bits are used.
+        // The last bit is ignored per the user manual!!!
+        ((self.value() & !0x1FF) >> 8) as u32
+    }
 }
 
 impl Add<Duration> for Instant {
@@ -211,6 +222,15 @@ impl Duration {
     pub fn value(&self) -> u64 {
         self.0
     }
+
+    /// Returns the value to write into the DX_TIME register for delays that
+    /// are relative to another timestamp
+    ///
+    /// The lowest 9 bits of the duration are ignored by the DW3000.
+    pub(crate) fn dx_time(&self) -> u32 {
+        // The duration is at most 40 bits wide, so this never truncates.
+        (self.value() >> 8) as u32
+    }
 }
 
 #[cfg(test)]
@@ -267,4 +287,19 @@ mod tests {
 
         assert_eq!(duration.value(), 383);
     }
+
+    #[test]
+    fn dx_time() {
+        let instant = Instant::new(0x12_3456_7800).unwrap();
+        assert_eq!(instant.dx_time(), 0x1234_5678);
+
+        let duration = Duration::new(0x12_3456_78ff).unwrap();
+        assert_eq!(duration.dx_time(), 0x1234_5678);
+    }
+
+    #[test]
+    fn dx_time_unrounded() {
+        let instant = Instant::new(0x12_3456_79ff).unwrap();
+        assert_eq!(instant.dx_time(), 0x1234_5678);
+    }
 }